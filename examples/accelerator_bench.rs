@@ -0,0 +1,88 @@
+// Traces the same rays through `random_scene` under each `Accelerator` and
+// checks that they all agree on what was hit.
+//
+//     cargo run --release --example accelerator_bench [rays]
+
+use std::{error::Error, time::Instant};
+
+use raytracer::camera::Camera;
+use raytracer::hittable::Accelerator;
+use raytracer::material::MaterialRegistry;
+use raytracer::ray::Ray;
+use raytracer::vec3::Vec3;
+use raytracer::*;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let ray_count = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
+
+    let camera = Camera::new(
+        Vec3(13.0, 2.0, 3.0),
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(0.0, 1.0, 0.0),
+        20.0,
+        16.0 / 9.0,
+        0.1,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    let rays: Vec<Ray> = (0..ray_count)
+        .map(|_| camera.get_ray(random_double(), random_double()))
+        .collect();
+
+    let mut registry = MaterialRegistry::with_presets();
+    let objects = random_scene_objects(&mut registry)?;
+    println!("{} objects, {} rays", objects.len(), rays.len());
+
+    let mut reference: Option<Vec<Option<f64>>> = None;
+
+    for &accelerator in &[
+        Accelerator::Bvh,
+        Accelerator::Bvh4,
+        Accelerator::Grid,
+        Accelerator::KdTree,
+    ] {
+        let start = Instant::now();
+        let world = accelerator.build(objects.clone(), 0.0, 1.0);
+        let build = start.elapsed().as_secs_f64();
+
+        let start = Instant::now();
+        let hits: Vec<Option<f64>> = rays
+            .iter()
+            .map(|ray| world.hit(*ray, 0.001, f64::MAX).map(|record| record.t))
+            .collect();
+        let elapsed = start.elapsed().as_secs_f64();
+
+        println!(
+            "{:<8} build {:>7.2} ms, trace {:>8.1} ms {:>6.2} Mrays/s",
+            format!("{:?}", accelerator),
+            build * 1000.0,
+            elapsed * 1000.0,
+            rays.len() as f64 / elapsed / 1e6
+        );
+
+        match &reference {
+            Some(reference) => {
+                let mismatches = reference
+                    .iter()
+                    .zip(&hits)
+                    .filter(|(a, b)| match (a, b) {
+                        (Some(a), Some(b)) => (a - b).abs() > 1e-9,
+                        (None, None) => false,
+                        _ => true,
+                    })
+                    .count();
+                if mismatches > 0 {
+                    println!("         {} rays disagree with Bvh", mismatches);
+                }
+            }
+            None => reference = Some(hits),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// Compares the trait-object `BvhNode` over a `HittableList` with the statically
+// dispatched `PrimitiveBvh` and four-wide `Bvh4` over a `PrimitiveList` on the
+// same scene and rays.
+//
+//     cargo run --release --example primitive_bench [--features simd] [rays]
+
+use std::rc::Rc;
+use std::time::Instant;
+
+use raytracer::camera::Camera;
+use raytracer::hittable::{
+    bvh_node::BvhNode, Bvh4, Hittable, HittableList, PrimitiveBvh, PrimitiveList, Sphere, Triangle,
+};
+use raytracer::material::{Lambertian, Material};
+use raytracer::ray::Ray;
+use raytracer::vec3::Vec3;
+use raytracer::*;
+
+fn scene() -> (HittableList, PrimitiveList) {
+    let material: Rc<dyn Material> = Rc::new(Lambertian::from((0.5, 0.5, 0.5)));
+    let mut objects = HittableList::new();
+    let mut primitives = PrimitiveList::new();
+
+    let ground = Sphere::new(Vec3(0.0, -1000.0, 0.0), 1000.0, material.clone());
+    objects.push(Rc::new(ground.clone()));
+    primitives.push(ground);
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let center = Vec3(
+                a as f64 + 0.9 * random_double(),
+                0.2,
+                b as f64 + 0.9 * random_double(),
+            );
+
+            if random_double() < 0.8 {
+                let sphere = Sphere::new(center, 0.2, material.clone());
+                objects.push(Rc::new(sphere.clone()));
+                primitives.push(sphere);
+            } else {
+                let triangle = Triangle::new(
+                    center + Vec3(-0.2, -0.2, 0.0),
+                    center + Vec3(0.2, -0.2, 0.0),
+                    center + Vec3(0.0, 0.2, 0.0),
+                    material.clone(),
+                );
+                objects.push(Rc::new(triangle.clone()));
+                primitives.push(triangle);
+            }
+        }
+    }
+
+    (objects, primitives)
+}
+
+fn trace<T: Hittable>(name: &str, world: &T, rays: &[Ray]) {
+    let start = Instant::now();
+    let hits = rays
+        .iter()
+        .filter(|ray| world.hit(**ray, 0.001, f64::MAX).is_some())
+        .count();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!(
+        "{:<14} {:>8.1} ms {:>8.2} Mrays/s ({} hits)",
+        name,
+        elapsed * 1000.0,
+        rays.len() as f64 / elapsed / 1e6,
+        hits
+    );
+}
+
+fn main() {
+    let ray_count = std::env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
+
+    let camera = Camera::new(
+        Vec3(13.0, 2.0, 3.0),
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(0.0, 1.0, 0.0),
+        20.0,
+        16.0 / 9.0,
+        0.0,
+        10.0,
+        0.0,
+        1.0,
+    );
+
+    let rays: Vec<Ray> = (0..ray_count)
+        .map(|_| camera.get_ray(random_double(), random_double()))
+        .collect();
+
+    let (objects, primitives) = scene();
+    println!("{} primitives, {} rays", primitives.len(), rays.len());
+
+    let bvh = BvhNode::new(objects, 0.0, 1.0);
+    let primitive_bvh = PrimitiveBvh::new(primitives.clone(), 0.0, 1.0);
+    let bvh4 = Bvh4::new(primitives, 0.0, 1.0);
+
+    trace("BvhNode", &bvh, &rays);
+    trace("PrimitiveBvh", &primitive_bvh, &rays);
+    trace("Bvh4", &bvh4, &rays);
+}
@@ -1,5 +1,9 @@
+use crate::packet::{Frustum, RayPacket};
 use crate::vec3::Vec3;
-use crate::{degrees_to_radians, ray::Ray};
+use crate::{
+    degrees_to_radians,
+    ray::{Ray, RayDifferential},
+};
 
 #[allow(dead_code)]
 pub struct Camera {
@@ -32,6 +36,7 @@ impl Default for Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Vec3,
         lookat: Vec3,
@@ -71,6 +76,14 @@ impl Camera {
         }
     }
 
+    pub fn time0(&self) -> f64 {
+        self.time0
+    }
+
+    pub fn time1(&self) -> f64 {
+        self.time1
+    }
+
     pub fn get_ray(&self, u: f64, v: f64) -> Ray {
         let rd = self.lens_radius * Vec3::rand_in_unit_disk();
         let offset = self.u * rd.x() + self.v * rd.y();
@@ -80,4 +93,56 @@ impl Camera {
             crate::rand_with_range(self.time0, self.time1),
         )
     }
+
+    // Rays from the same point on the lens as `ray`, one pixel of `du` and `dv`
+    // along. `ray` must have come from `get_ray`.
+    pub fn differential(&self, ray: &Ray, du: f64, dv: f64) -> RayDifferential {
+        // Camera rays reach the focus plane at t = 1, so moving a pixel there only
+        // changes the direction.
+        RayDifferential {
+            rx: Ray::new(ray.origin(), ray.direction() + du * self.horizontal, ray.time()),
+            ry: Ray::new(ray.origin(), ray.direction() + dv * self.vertical, ray.time()),
+        }
+    }
+
+    pub fn get_ray_packet(&self, uvs: &[(f64, f64)]) -> RayPacket {
+        let rays = uvs.iter().map(|&(u, v)| self.get_ray(u, v)).collect();
+
+        if uvs.is_empty() {
+            return RayPacket::new(rays, None);
+        }
+
+        let (u0, u1, v0, v1) = uvs.iter().fold(
+            (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+            |(u0, u1, v0, v1), &(u, v)| (u0.min(u), u1.max(u), v0.min(v), v1.max(v)),
+        );
+
+        let focus =
+            |u: f64, v: f64| self.lower_left_corner + u * self.horizontal + v * self.vertical;
+        let r = self.lens_radius;
+
+        // Every ray starts on the lens disk and passes through the tile's footprint
+        // on the focus plane. Each side plane holds one edge of that footprint and
+        // the rim of the lens on the same side, tilted to also clear the rays from
+        // the opposite rim that cross over behind the focus plane.
+        let side = |outward: Vec3, along: Vec3, edge: Vec3| {
+            (
+                self.origin + r * outward,
+                along,
+                edge - (self.origin - r * outward),
+            )
+        };
+
+        let frustum = Frustum::new(
+            [
+                side(self.v, self.u, focus(u0, v1)),
+                side(-self.v, self.u, focus(u0, v0)),
+                side(self.u, self.v, focus(u1, v0)),
+                side(-self.u, self.v, focus(u0, v0)),
+            ],
+            focus(0.5 * (u0 + u1), 0.5 * (v0 + v1)),
+        );
+
+        RayPacket::new(rays, Some(frustum))
+    }
 }
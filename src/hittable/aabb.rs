@@ -1,4 +1,8 @@
-use crate::{ray::Ray, vec3::Vec3};
+use crate::{
+    ray::{InvRay, Ray},
+    stats,
+    vec3::Vec3,
+};
 
 use super::Hittable;
 use std::{cmp::Ordering, rc::Rc};
@@ -38,25 +42,45 @@ impl Aabb {
         self.max
     }
 
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.max - self.min;
+        2.0 * (extent.x() * extent.y() + extent.y() * extent.z() + extent.z() * extent.x())
+    }
+
     pub fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> bool {
+        self.hit_inv(&InvRay::from(ray), tmin, tmax)
+    }
+
+    // Returns the part of [tmin, tmax] for which the ray is inside the box.
+    pub fn intersect(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<(f64, f64)> {
+        self.intersect_inv(&InvRay::from(ray), tmin, tmax)
+    }
+
+    pub fn hit_inv(&self, ray: &InvRay, tmin: f64, tmax: f64) -> bool {
+        self.intersect_inv(ray, tmin, tmax).is_some()
+    }
+
+    pub fn intersect_inv(&self, ray: &InvRay, mut tmin: f64, mut tmax: f64) -> Option<(f64, f64)> {
+        stats::count_box_test();
+
         for i in 0..3 {
-            let inv_d = 1.0 / ray.direction().get(i);
-            let mut t0 = (self.min.get(i) - ray.origin().get(i)) * inv_d;
-            let mut t1 = (self.max.get(i) - ray.origin().get(i)) * inv_d;
+            let inv_d = ray.inv_dir.get(i);
+            let mut t0 = (self.min.get(i) - ray.origin.get(i)) * inv_d;
+            let mut t1 = (self.max.get(i) - ray.origin.get(i)) * inv_d;
 
             if inv_d < 0.0 {
                 std::mem::swap(&mut t0, &mut t1);
             }
 
-            let tmin = f64::max(t0, tmin);
-            let tmax = f64::min(t1, tmax);
+            tmin = f64::max(t0, tmin);
+            tmax = f64::min(t1, tmax);
 
             if tmax <= tmin {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((tmin, tmax))
     }
 }
 
@@ -0,0 +1,271 @@
+use super::{
+    aabb::Aabb,
+    primitive::{sort_along_widest_axis, surrounding_box, PrimitiveRef},
+    HitRecord, Hittable, PrimitiveList,
+};
+use crate::{
+    ray::{InvRay, Ray},
+    simd::{Bounds4, Isa, Triangle8, LANES, TRIANGLE_LANES},
+    stats,
+};
+
+const MAX_LEAF_SIZE: usize = 4;
+// Each level leaves at most three siblings on the stack besides the child it
+// descends into, so this covers trees 32 levels deep; median splits into four
+// never get close.
+const STACK_SIZE: usize = 96;
+
+#[derive(Debug, Copy, Clone)]
+enum Child {
+    Node(u32),
+    // Non-triangle primitives are `refs[offset..offset + count]`; triangles are
+    // packed eight at a time into `packets[packet_offset..packet_offset + packets]`.
+    Leaf {
+        offset: u32,
+        count: u32,
+        packet_offset: u32,
+        packets: u32,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Node4 {
+    bounds: Bounds4,
+    children: [Child; LANES],
+    len: usize,
+}
+
+/// A four-wide BVH over a `PrimitiveList`. Each node tests the boxes of all its
+/// children in one go, and leaf triangles are intersected up to eight at a time.
+pub struct Bvh4 {
+    primitives: PrimitiveList,
+    isa: Isa,
+    refs: Vec<PrimitiveRef>,
+    packets: Vec<Triangle8>,
+    nodes: Vec<Node4>,
+    bounding_box: Option<Aabb>,
+}
+
+impl Bvh4 {
+    pub fn new(primitives: PrimitiveList, time0: f64, time1: f64) -> Self {
+        let mut bounds: Vec<(PrimitiveRef, Aabb)> = primitives
+            .refs()
+            .iter()
+            .map(|&primitive| {
+                let bounding_box = primitives
+                    .bounding_box_ref(primitive, time0, time1)
+                    .expect("No bounding box in Bvh4 constructor.");
+                (primitive, bounding_box)
+            })
+            .collect();
+
+        let mut bvh = Bvh4 {
+            primitives,
+            isa: Isa::detect(),
+            refs: Vec::new(),
+            packets: Vec::new(),
+            nodes: Vec::new(),
+            bounding_box: None,
+        };
+
+        if !bounds.is_empty() {
+            bvh.bounding_box = Some(surrounding_box(&bounds));
+            bvh.build(&mut bounds);
+        }
+
+        bvh
+    }
+
+    fn build(&mut self, bounds: &mut [(PrimitiveRef, Aabb)]) -> u32 {
+        let index = self.nodes.len();
+        self.nodes.push(Node4 {
+            bounds: Bounds4::default(),
+            children: [Child::Node(0); LANES],
+            len: 0,
+        });
+
+        // Split in half, then split each half again, to get up to four children.
+        let mut groups: Vec<&mut [(PrimitiveRef, Aabb)]> = Vec::with_capacity(LANES);
+        if fits_in_leaf(bounds) {
+            groups.push(bounds);
+        } else {
+            sort_along_widest_axis(bounds);
+            let mid = bounds.len() / 2;
+            let (left, right) = bounds.split_at_mut(mid);
+
+            for half in [left, right] {
+                if fits_in_leaf(half) {
+                    groups.push(half);
+                } else {
+                    sort_along_widest_axis(half);
+                    let mid = half.len() / 2;
+                    let (left, right) = half.split_at_mut(mid);
+                    groups.push(left);
+                    groups.push(right);
+                }
+            }
+        }
+
+        let mut node_bounds = Bounds4::default();
+        let mut children = [Child::Node(0); LANES];
+        let len = groups.len();
+
+        for (lane, group) in groups.into_iter().enumerate() {
+            node_bounds.set(lane, surrounding_box(group));
+            children[lane] = if fits_in_leaf(group) {
+                self.leaf(group)
+            } else {
+                Child::Node(self.build(group))
+            };
+        }
+
+        self.nodes[index] = Node4 {
+            bounds: node_bounds,
+            children,
+            len,
+        };
+
+        index as u32
+    }
+
+    fn leaf(&mut self, group: &[(PrimitiveRef, Aabb)]) -> Child {
+        let offset = self.refs.len() as u32;
+        let packet_offset = self.packets.len() as u32;
+        let mut triangles = Vec::new();
+
+        for &(primitive, _) in group {
+            match primitive {
+                PrimitiveRef::Triangle(id) => {
+                    triangles.push((id, self.primitives.triangle_vertices(id)))
+                }
+                _ => self.refs.push(primitive),
+            }
+        }
+
+        for chunk in triangles.chunks(TRIANGLE_LANES) {
+            self.packets.push(Triangle8::new(chunk));
+        }
+
+        Child::Leaf {
+            offset,
+            count: self.refs.len() as u32 - offset,
+            packet_offset,
+            packets: self.packets.len() as u32 - packet_offset,
+        }
+    }
+
+    pub fn primitives(&self) -> &PrimitiveList {
+        &self.primitives
+    }
+}
+
+// Up to four primitives make a leaf, or up to eight if they're all triangles, since
+// those fit in a single packet.
+fn fits_in_leaf(group: &[(PrimitiveRef, Aabb)]) -> bool {
+    group.len() <= MAX_LEAF_SIZE
+        || (group.len() <= TRIANGLE_LANES
+            && group
+                .iter()
+                .all(|(primitive, _)| matches!(primitive, PrimitiveRef::Triangle(_))))
+}
+
+impl Hittable for Bvh4 {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_ray = InvRay::from(&ray);
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+
+        let mut stack = [(Child::Node(0), min); STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (child, t_near) = stack[stack_len];
+            if t_near >= closest_so_far {
+                continue;
+            }
+
+            match child {
+                Child::Node(index) => {
+                    let node = &self.nodes[index as usize];
+                    let (near, mask) =
+                        self.isa
+                            .hit_boxes4(&node.bounds, &inv_ray, min, closest_so_far);
+                    stats::count_box_tests(node.len as u64);
+
+                    // Push furthest first so the nearest child is visited next.
+                    let mut hits = [0; LANES];
+                    let mut hit_count = 0;
+                    for lane in (0..node.len).filter(|&lane| mask & (1 << lane) != 0) {
+                        hits[hit_count] = lane;
+                        hit_count += 1;
+                    }
+                    let hits = &mut hits[..hit_count];
+                    hits.sort_unstable_by(|&a, &b| near[b].partial_cmp(&near[a]).unwrap());
+
+                    for &lane in hits.iter() {
+                        stack[stack_len] = (node.children[lane], near[lane]);
+                        stack_len += 1;
+                    }
+                }
+                Child::Leaf {
+                    offset,
+                    count,
+                    packet_offset,
+                    packets,
+                } => {
+                    let start = offset as usize;
+                    stats::count_primitive_tests(count as u64);
+                    for &primitive in &self.refs[start..start + count as usize] {
+                        if let Some(record) =
+                            self.primitives.hit_ref(primitive, ray, min, closest_so_far)
+                        {
+                            closest_so_far = record.t;
+                            hit_anything = Some(record);
+                        }
+                    }
+
+                    let start = packet_offset as usize;
+                    for packet in &self.packets[start..start + packets as usize] {
+                        let ts = self
+                            .isa
+                            .hit_triangles(packet, &inv_ray, min, closest_so_far);
+                        stats::count_primitive_tests(packet.len as u64);
+
+                        let mut lanes = [0; TRIANGLE_LANES];
+                        let mut lane_count = 0;
+                        for lane in (0..packet.len).filter(|&lane| ts[lane] < closest_so_far) {
+                            lanes[lane_count] = lane;
+                            lane_count += 1;
+                        }
+                        let lanes = &mut lanes[..lane_count];
+                        lanes.sort_unstable_by(|&a, &b| ts[a].partial_cmp(&ts[b]).unwrap());
+
+                        // The packet test only finds candidates; the scalar test
+                        // builds the record and gets the final say.
+                        for &lane in lanes.iter() {
+                            let triangle = PrimitiveRef::Triangle(packet.ids[lane]);
+                            if let Some(record) =
+                                self.primitives.hit_ref(triangle, ray, min, closest_so_far)
+                            {
+                                closest_so_far = record.t;
+                                hit_anything = Some(record);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
@@ -1,12 +1,100 @@
 use super::{aabb::*, HitRecord, Hittable, HittableList};
-use crate::ray::Ray;
+use crate::{
+    packet::RayPacket,
+    ray::{InvRay, Ray},
+    stats,
+};
 use rand::Rng;
-use std::rc::Rc;
+use std::{fmt, rc::Rc};
+
+// Relative costs used for the SAH estimate of a finished tree.
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct BvhStats {
+    pub depth: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub primitive_count: usize,
+    pub min_leaf_size: usize,
+    pub max_leaf_size: usize,
+    pub sah_cost: f64,
+}
+
+impl BvhStats {
+    fn leaf(size: usize) -> Self {
+        BvhStats {
+            depth: 1,
+            node_count: 1,
+            leaf_count: 1,
+            primitive_count: size,
+            min_leaf_size: size,
+            max_leaf_size: size,
+            sah_cost: INTERSECTION_COST * size as f64,
+        }
+    }
+
+    fn interior(left: (Self, Aabb), right: (Self, Aabb), bounding_box: Aabb) -> Self {
+        let (left, box_left) = left;
+        let (right, box_right) = right;
+        let area = bounding_box.surface_area();
+
+        // Degenerate (flat) bounds give no useful probability, so split evenly.
+        let (p_left, p_right) = if area > 0.0 {
+            (
+                box_left.surface_area() / area,
+                box_right.surface_area() / area,
+            )
+        } else {
+            (0.5, 0.5)
+        };
+
+        BvhStats {
+            depth: 1 + usize::max(left.depth, right.depth),
+            node_count: 1 + left.node_count + right.node_count,
+            leaf_count: left.leaf_count + right.leaf_count,
+            primitive_count: left.primitive_count + right.primitive_count,
+            min_leaf_size: usize::min(left.min_leaf_size, right.min_leaf_size),
+            max_leaf_size: usize::max(left.max_leaf_size, right.max_leaf_size),
+            sah_cost: TRAVERSAL_COST + p_left * left.sah_cost + p_right * right.sah_cost,
+        }
+    }
+
+    pub fn average_leaf_size(&self) -> f64 {
+        if self.leaf_count == 0 {
+            return 0.0;
+        }
+
+        self.primitive_count as f64 / self.leaf_count as f64
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "BVH depth: {}", self.depth)?;
+        writeln!(
+            f,
+            "BVH nodes: {} ({} leaves, {} primitives)",
+            self.node_count, self.leaf_count, self.primitive_count
+        )?;
+        writeln!(
+            f,
+            "BVH leaf size: min {}, max {}, avg {:.2}",
+            self.min_leaf_size,
+            self.max_leaf_size,
+            self.average_leaf_size()
+        )?;
+        write!(f, "BVH SAH cost: {:.3}", self.sah_cost)
+    }
+}
 
 pub struct BvhNode {
     left: Rc<dyn Hittable>,
     right: Rc<dyn Hittable>,
     bounding_box: Aabb,
+    is_leaf: bool,
+    stats: BvhStats,
 }
 
 impl BvhNode {
@@ -24,20 +112,21 @@ impl BvhNode {
             _ => box_z_compare,
         };
 
-        let (left, right) = match objects.len() {
-            1 => (Rc::clone(&objects[0]), Rc::clone(&objects[0])),
+        let (left, right, children) = match objects.len() {
+            1 => (Rc::clone(&objects[0]), Rc::clone(&objects[0]), None),
             2 => match comparator(&objects[0], &objects[1]) {
-                std::cmp::Ordering::Less => (Rc::clone(&objects[0]), Rc::clone(&objects[1])),
-                _ => (Rc::clone(&objects[1]), Rc::clone(&objects[0])),
+                std::cmp::Ordering::Less => (Rc::clone(&objects[0]), Rc::clone(&objects[1]), None),
+                _ => (Rc::clone(&objects[1]), Rc::clone(&objects[0]), None),
             },
             _ => {
                 objects.sort_by(comparator);
                 let mid = objects.len() / 2;
-                let left: Rc<dyn Hittable> =
-                    Rc::new(Self::create(&mut objects[0..mid], time0, time1));
-                let right: Rc<dyn Hittable> =
-                    Rc::new(Self::create(&mut objects[mid..], time0, time1));
-                (left, right)
+                let left = Self::create(&mut objects[0..mid], time0, time1);
+                let right = Self::create(&mut objects[mid..], time0, time1);
+                let children = Some((left.stats, right.stats));
+                let left: Rc<dyn Hittable> = Rc::new(left);
+                let right: Rc<dyn Hittable> = Rc::new(right);
+                (left, right, children)
             }
         };
 
@@ -46,32 +135,167 @@ impl BvhNode {
 
         let bounding_box = Aabb::surrounding_box(box_left, box_right);
 
+        let stats = match children {
+            Some((stats_left, stats_right)) => BvhStats::interior(
+                (stats_left, box_left),
+                (stats_right, box_right),
+                bounding_box,
+            ),
+            None => BvhStats::leaf(objects.len()),
+        };
+
         Self {
             left,
             right,
             bounding_box,
+            is_leaf: children.is_none(),
+            stats,
         }
     }
+
+    pub fn stats(&self) -> BvhStats {
+        self.stats
+    }
+
+    // A leaf made from a single object holds it as both children, but it should
+    // only be intersected once.
+    fn children(&self) -> impl Iterator<Item = &Rc<dyn Hittable>> {
+        let right = if Rc::ptr_eq(&self.left, &self.right) {
+            None
+        } else {
+            Some(&self.right)
+        };
+
+        std::iter::once(&self.left).chain(right)
+    }
 }
 
 impl Hittable for BvhNode {
     fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
-        if !self.bounding_box.hit(&ray, min, max) {
+        self.hit_inv(ray, &InvRay::from(&ray), min, max)
+    }
+
+    fn hit_inv(&self, ray: Ray, inv_ray: &InvRay, min: f64, max: f64) -> Option<HitRecord> {
+        if !self.bounding_box.hit_inv(inv_ray, min, max) {
             return None;
         }
 
-        match self.left.hit(ray, min, max) {
-            Some(record) => {
-                let max = record.t;
-                match self.right.hit(ray, min, max) {
-                    Some(record) => Some(record),
-                    None => Some(record),
-                }
+        if self.is_leaf {
+            stats::count_primitive_tests(self.children().count() as u64);
+        }
+
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+        for child in self.children() {
+            if let Some(record) = child.hit_inv(ray, inv_ray, min, closest_so_far) {
+                closest_so_far = record.t;
+                hit_anything = Some(record);
+            }
+        }
+
+        hit_anything
+    }
+
+    fn hit_packet(
+        &self,
+        packet: &RayPacket,
+        min: f64,
+        max: f64,
+        records: &mut [Option<HitRecord>],
+    ) {
+        if let Some(frustum) = packet.frustum() {
+            if frustum.culls(&self.bounding_box) {
+                return;
             }
-            None => self.right.hit(ray, min, max),
+        }
+
+        // The frustum is conservative, so only descend if some ray really enters
+        // the box before its current closest hit.
+        let entered = packet
+            .inv_rays()
+            .iter()
+            .zip(records.iter())
+            .any(|(inv_ray, record)| {
+                let closest_so_far = record.as_ref().map_or(max, |record| record.t);
+                self.bounding_box.hit_inv(inv_ray, min, closest_so_far)
+            });
+
+        if !entered {
+            return;
+        }
+
+        if self.is_leaf {
+            stats::count_primitive_tests((self.children().count() * packet.len()) as u64);
+        }
+
+        for child in self.children() {
+            child.hit_packet(packet, min, max, records);
         }
     }
+
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
         Some(self.bounding_box)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::material::MaterialRegistry;
+    use crate::vec3::Vec3;
+    use crate::{random_double, random_scene_objects};
+
+    #[test]
+    fn packets_find_the_same_hits_as_single_rays() {
+        let mut registry = MaterialRegistry::with_presets();
+        let bvh = BvhNode::new(random_scene_objects(&mut registry).unwrap(), 0.0, 1.0);
+
+        // Defocused, so the frustum has to allow for the lens as well.
+        let camera = Camera::new(
+            Vec3(13.0, 2.0, 3.0),
+            Vec3(0.0, 0.0, 0.0),
+            Vec3(0.0, 1.0, 0.0),
+            20.0,
+            16.0 / 9.0,
+            0.1,
+            10.0,
+            0.0,
+            1.0,
+        );
+
+        let (width, height, tile) = (96, 54, 8);
+        for tile_y in (0..height).step_by(tile) {
+            for tile_x in (0..width).step_by(tile) {
+                let uvs: Vec<(f64, f64)> = (tile_y..(tile_y + tile).min(height))
+                    .flat_map(|y| (tile_x..(tile_x + tile).min(width)).map(move |x| (x, y)))
+                    .map(|(x, y)| {
+                        let u = (x as f64 + random_double()) / width as f64;
+                        let v = 1.0 - (y as f64 + random_double()) / height as f64;
+                        (u, v)
+                    })
+                    .collect();
+
+                let packet = camera.get_ray_packet(&uvs);
+                let mut records = vec![None; packet.len()];
+                bvh.hit_packet(&packet, 0.001, f64::MAX, &mut records);
+
+                for (ray, record) in packet.rays().iter().zip(&records) {
+                    match (bvh.hit(*ray, 0.001, f64::MAX), record) {
+                        (None, None) => {}
+                        (Some(expected), Some(found)) => {
+                            assert_eq!(expected.t, found.t);
+                            assert!(Rc::ptr_eq(&expected.mat_ptr, &found.mat_ptr));
+                        }
+                        (expected, found) => panic!(
+                            "ray {:?} hit at {:?} alone but {:?} in a packet",
+                            ray,
+                            expected.map(|record| record.t),
+                            found.as_ref().map(|record| record.t)
+                        ),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::{aabb::Aabb, HitRecord, Hittable, HittableList};
+use crate::{ray::Ray, stats, vec3::Vec3};
+
+// Target number of objects per cell when choosing the grid resolution.
+const DENSITY: f64 = 3.0;
+const MAX_RESOLUTION: usize = 128;
+
+// Objects whose bounds are this large a fraction of the whole scene (ground
+// spheres, skies) would land in nearly every cell, so they are kept out of the
+// grid and tested against every ray instead.
+const LARGE_OBJECT_FRACTION: f64 = 0.25;
+
+/// A uniform grid traversed with a 3D-DDA, best suited to scenes of many small,
+/// evenly distributed objects.
+pub struct UniformGrid {
+    objects: HittableList,
+    large_objects: Vec<u32>,
+    bounding_box: Option<Aabb>,
+    grid_box: Aabb,
+    resolution: [usize; 3],
+    cell_size: Vec3,
+    // Cell `i` holds `cell_objects[cell_start[i]..cell_start[i + 1]]`.
+    cell_start: Vec<u32>,
+    cell_objects: Vec<u32>,
+}
+
+impl UniformGrid {
+    pub fn new(objects: HittableList, time0: f64, time1: f64) -> Self {
+        let boxes: Vec<Aabb> = objects
+            .iter()
+            .map(|object| {
+                object
+                    .bounding_box(time0, time1)
+                    .expect("No bounding box in UniformGrid constructor.")
+            })
+            .collect();
+
+        let bounding_box = boxes.iter().copied().reduce(Aabb::surrounding_box);
+        let scene_diagonal = bounding_box.map_or(0.0, |b| (b.max() - b.min()).length());
+
+        let (large, small): (Vec<usize>, Vec<usize>) = (0..boxes.len()).partition(|&i| {
+            (boxes[i].max() - boxes[i].min()).length() > LARGE_OBJECT_FRACTION * scene_diagonal
+        });
+
+        let grid_box = small
+            .iter()
+            .map(|&i| boxes[i])
+            .reduce(Aabb::surrounding_box)
+            .unwrap_or_default();
+
+        // Pad so that flat scenes still have some extent along every axis.
+        let padding = Vec3(0.0001, 0.0001, 0.0001);
+        let grid_box = Aabb::new(grid_box.min() - padding, grid_box.max() + padding);
+        let extent = grid_box.max() - grid_box.min();
+
+        let volume = extent.x() * extent.y() * extent.z();
+        let cells_per_unit = (DENSITY * small.len() as f64 / volume).cbrt();
+        let mut resolution = [1; 3];
+        for (axis, resolution) in resolution.iter_mut().enumerate() {
+            let cells = (extent.get(axis) * cells_per_unit).round() as usize;
+            *resolution = cells.clamp(1, MAX_RESOLUTION);
+        }
+
+        let cell_size = Vec3(
+            extent.x() / resolution[0] as f64,
+            extent.y() / resolution[1] as f64,
+            extent.z() / resolution[2] as f64,
+        );
+
+        let mut grid = UniformGrid {
+            objects,
+            large_objects: large.into_iter().map(|i| i as u32).collect(),
+            bounding_box,
+            grid_box,
+            resolution,
+            cell_size,
+            cell_start: Vec::new(),
+            cell_objects: Vec::new(),
+        };
+
+        let mut cells = vec![Vec::new(); resolution[0] * resolution[1] * resolution[2]];
+        for &i in &small {
+            let lo = grid.cell_of(boxes[i].min());
+            let hi = grid.cell_of(boxes[i].max());
+
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        cells[grid.cell_index([x, y, z])].push(i as u32);
+                    }
+                }
+            }
+        }
+
+        grid.cell_start.push(0);
+        for cell in cells {
+            grid.cell_objects.extend(cell);
+            grid.cell_start.push(grid.cell_objects.len() as u32);
+        }
+
+        grid
+    }
+
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    fn cell_of(&self, p: Vec3) -> [usize; 3] {
+        let mut cell = [0; 3];
+        for (axis, cell) in cell.iter_mut().enumerate() {
+            let offset = (p.get(axis) - self.grid_box.min().get(axis)) / self.cell_size.get(axis);
+            *cell = (offset.max(0.0) as usize).min(self.resolution[axis] - 1);
+        }
+        cell
+    }
+
+    fn cell_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    fn hit_objects(
+        &self,
+        indices: &[u32],
+        ray: Ray,
+        min: f64,
+        closest_so_far: &mut f64,
+        hit_anything: &mut Option<HitRecord>,
+    ) {
+        stats::count_primitive_tests(indices.len() as u64);
+
+        for &i in indices {
+            if let Some(record) = self.objects[i as usize].hit(ray, min, *closest_so_far) {
+                *closest_so_far = record.t;
+                *hit_anything = Some(record);
+            }
+        }
+    }
+}
+
+impl Hittable for UniformGrid {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+
+        self.hit_objects(
+            &self.large_objects,
+            ray,
+            min,
+            &mut closest_so_far,
+            &mut hit_anything,
+        );
+
+        let (t_enter, t_exit) = match self.grid_box.intersect(&ray, min, closest_so_far) {
+            Some(range) => range,
+            None => return hit_anything,
+        };
+
+        let entry = ray.at(t_enter);
+        let mut cell = self.cell_of(entry);
+        let mut step = [0isize; 3];
+        let mut next_t = [f64::INFINITY; 3];
+        let mut delta_t = [f64::INFINITY; 3];
+
+        for axis in 0..3 {
+            let d = ray.direction().get(axis);
+            let size = self.cell_size.get(axis);
+            let cell_min = self.grid_box.min().get(axis) + cell[axis] as f64 * size;
+
+            if d > 0.0 {
+                step[axis] = 1;
+                next_t[axis] = t_enter + (cell_min + size - entry.get(axis)) / d;
+                delta_t[axis] = size / d;
+            } else if d < 0.0 {
+                step[axis] = -1;
+                next_t[axis] = t_enter + (cell_min - entry.get(axis)) / d;
+                delta_t[axis] = -size / d;
+            }
+        }
+
+        loop {
+            let index = self.cell_index(cell);
+            let start = self.cell_start[index] as usize;
+            let end = self.cell_start[index + 1] as usize;
+            self.hit_objects(
+                &self.cell_objects[start..end],
+                ray,
+                min,
+                &mut closest_so_far,
+                &mut hit_anything,
+            );
+
+            let axis = if next_t[0] < next_t[1] && next_t[0] < next_t[2] {
+                0
+            } else if next_t[1] < next_t[2] {
+                1
+            } else {
+                2
+            };
+
+            // Anything hit before we leave this cell can't be beaten further on.
+            if closest_so_far <= next_t[axis] || next_t[axis] > t_exit {
+                break;
+            }
+
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= self.resolution[axis] as isize {
+                break;
+            }
+
+            cell[axis] = next as usize;
+            next_t[axis] += delta_t[axis];
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
@@ -0,0 +1,345 @@
+use super::{aabb::Aabb, HitRecord, Hittable, HittableList};
+use crate::{ray::Ray, stats};
+
+// SAH parameters, following the usual defaults from PBRT.
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 80.0;
+const EMPTY_BONUS: f64 = 0.5;
+const MAX_LEAF_SIZE: usize = 1;
+
+#[derive(Debug, Copy, Clone)]
+enum KdNode {
+    // Holds `indices[start..start + count]`.
+    Leaf { start: u32, count: u32 },
+    // The child below the split plane directly follows its parent.
+    Interior { axis: usize, split: f64, above: u32 },
+}
+
+#[derive(Copy, Clone)]
+struct Edge {
+    t: f64,
+    object: u32,
+    starting: bool,
+}
+
+/// A k-d tree whose split planes are chosen with the surface area heuristic.
+/// Objects straddling a plane are referenced from both sides.
+pub struct KdTree {
+    objects: HittableList,
+    bounding_box: Option<Aabb>,
+    nodes: Vec<KdNode>,
+    indices: Vec<u32>,
+}
+
+impl KdTree {
+    pub fn new(objects: HittableList, time0: f64, time1: f64) -> Self {
+        let boxes: Vec<Aabb> = objects
+            .iter()
+            .map(|object| {
+                object
+                    .bounding_box(time0, time1)
+                    .expect("No bounding box in KdTree constructor.")
+            })
+            .collect();
+
+        let bounding_box = boxes.iter().copied().reduce(Aabb::surrounding_box);
+
+        let mut tree = KdTree {
+            objects,
+            bounding_box,
+            nodes: Vec::new(),
+            indices: Vec::new(),
+        };
+
+        if let Some(bounds) = bounding_box {
+            let max_depth = (8.0 + 1.3 * (boxes.len() as f64).log2()).round() as usize;
+            let all = (0..boxes.len() as u32).collect();
+            tree.build(&boxes, bounds, all, max_depth, 0);
+        }
+
+        tree
+    }
+
+    fn build(
+        &mut self,
+        boxes: &[Aabb],
+        bounds: Aabb,
+        objects: Vec<u32>,
+        depth: usize,
+        mut bad_refines: usize,
+    ) {
+        if objects.len() <= MAX_LEAF_SIZE || depth == 0 {
+            self.push_leaf(&objects);
+            return;
+        }
+
+        let leaf_cost = INTERSECTION_COST * objects.len() as f64;
+        let (best_cost, axis, split) = match Self::find_split(boxes, bounds, &objects) {
+            Some(split) => split,
+            None => {
+                self.push_leaf(&objects);
+                return;
+            }
+        };
+
+        if best_cost > leaf_cost {
+            bad_refines += 1;
+        }
+
+        if (best_cost > 4.0 * leaf_cost && objects.len() < 16) || bad_refines == 3 {
+            self.push_leaf(&objects);
+            return;
+        }
+
+        // An object lying in the split plane goes above, so one side always
+        // keeps it.
+        let below: Vec<u32> = objects
+            .iter()
+            .copied()
+            .filter(|&i| boxes[i as usize].min().get(axis) < split)
+            .collect();
+        let above: Vec<u32> = objects
+            .iter()
+            .copied()
+            .filter(|&i| boxes[i as usize].max().get(axis) >= split)
+            .collect();
+
+        let (bounds_below, bounds_above) = split_box(bounds, axis, split);
+
+        let index = self.nodes.len();
+        self.nodes.push(KdNode::Interior {
+            axis,
+            split,
+            above: 0,
+        });
+
+        self.build(boxes, bounds_below, below, depth - 1, bad_refines);
+
+        let above_index = self.nodes.len() as u32;
+        if let KdNode::Interior { above, .. } = &mut self.nodes[index] {
+            *above = above_index;
+        }
+
+        self.build(boxes, bounds_above, above, depth - 1, bad_refines);
+    }
+
+    fn push_leaf(&mut self, objects: &[u32]) {
+        self.nodes.push(KdNode::Leaf {
+            start: self.indices.len() as u32,
+            count: objects.len() as u32,
+        });
+        self.indices.extend_from_slice(objects);
+    }
+
+    // Sweeps the bounding box edges of every object along each axis and returns the
+    // cheapest (cost, axis, position) split strictly inside `bounds`.
+    fn find_split(boxes: &[Aabb], bounds: Aabb, objects: &[u32]) -> Option<(f64, usize, f64)> {
+        let extent = bounds.max() - bounds.min();
+        let total_area = bounds.surface_area();
+        let inv_total_area = 1.0 / total_area;
+        let mut best: Option<(f64, usize, f64)> = None;
+
+        for axis in 0..3 {
+            let other0 = (axis + 1) % 3;
+            let other1 = (axis + 2) % 3;
+
+            let mut edges: Vec<Edge> = Vec::with_capacity(2 * objects.len());
+            for &object in objects {
+                let b = boxes[object as usize];
+                edges.push(Edge {
+                    t: b.min().get(axis),
+                    object,
+                    starting: true,
+                });
+                edges.push(Edge {
+                    t: b.max().get(axis),
+                    object,
+                    starting: false,
+                });
+            }
+
+            // Starting edges sort first on ties so a zero-width object is
+            // counted on both sides of a plane through it.
+            edges.sort_by(|a, b| {
+                a.t.partial_cmp(&b.t)
+                    .unwrap()
+                    .then(b.starting.cmp(&a.starting))
+                    .then(a.object.cmp(&b.object))
+            });
+
+            let mut below = 0;
+            let mut above = objects.len();
+
+            for edge in &edges {
+                if !edge.starting {
+                    above -= 1;
+                }
+
+                let t = edge.t;
+                if t > bounds.min().get(axis) && t < bounds.max().get(axis) {
+                    let d0 = extent.get(other0);
+                    let d1 = extent.get(other1);
+                    let area_below = 2.0 * (d0 * d1 + (t - bounds.min().get(axis)) * (d0 + d1));
+                    let area_above = 2.0 * (d0 * d1 + (bounds.max().get(axis) - t) * (d0 + d1));
+                    let p_below = area_below * inv_total_area;
+                    let p_above = area_above * inv_total_area;
+                    let bonus = if below == 0 || above == 0 {
+                        EMPTY_BONUS
+                    } else {
+                        0.0
+                    };
+                    let cost = TRAVERSAL_COST
+                        + INTERSECTION_COST
+                            * (1.0 - bonus)
+                            * (p_below * below as f64 + p_above * above as f64);
+
+                    let better = match best {
+                        Some((best_cost, _, _)) => cost < best_cost,
+                        None => true,
+                    };
+                    if better {
+                        best = Some((cost, axis, t));
+                    }
+                }
+
+                if edge.starting {
+                    below += 1;
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn split_box(bounds: Aabb, axis: usize, split: f64) -> (Aabb, Aabb) {
+    let mut below_max = bounds.max();
+    let mut above_min = bounds.min();
+
+    match axis {
+        0 => {
+            below_max.0 = split;
+            above_min.0 = split;
+        }
+        1 => {
+            below_max.1 = split;
+            above_min.1 = split;
+        }
+        _ => {
+            below_max.2 = split;
+            above_min.2 = split;
+        }
+    }
+
+    (
+        Aabb::new(bounds.min(), below_max),
+        Aabb::new(above_min, bounds.max()),
+    )
+}
+
+impl Hittable for KdTree {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        let bounds = self.bounding_box?;
+        let (mut t_min, mut t_max) = bounds.intersect(&ray, min, max)?;
+
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+        let mut stack: Vec<(usize, f64, f64)> = Vec::with_capacity(64);
+        let mut node = 0;
+
+        loop {
+            // Everything left is further away than what we've already hit.
+            if closest_so_far < t_min {
+                break;
+            }
+
+            match self.nodes[node] {
+                KdNode::Interior { axis, split, above } => {
+                    let origin = ray.origin().get(axis);
+                    let direction = ray.direction().get(axis);
+                    let t_plane = (split - origin) / direction;
+
+                    let below_first = origin < split || (origin == split && direction <= 0.0);
+                    let (first, second) = if below_first {
+                        (node + 1, above as usize)
+                    } else {
+                        (above as usize, node + 1)
+                    };
+
+                    if t_plane > t_max || t_plane <= 0.0 {
+                        node = first;
+                    } else if t_plane < t_min {
+                        node = second;
+                    } else {
+                        stack.push((second, t_plane, t_max));
+                        node = first;
+                        t_max = t_plane;
+                    }
+                }
+                KdNode::Leaf { start, count } => {
+                    let start = start as usize;
+                    stats::count_primitive_tests(count as u64);
+
+                    for &i in &self.indices[start..start + count as usize] {
+                        let object = &self.objects[i as usize];
+                        if let Some(record) = object.hit(ray, min, closest_so_far) {
+                            closest_so_far = record.t;
+                            hit_anything = Some(record);
+                        }
+                    }
+
+                    match stack.pop() {
+                        Some((next, next_min, next_max)) => {
+                            node = next;
+                            t_min = next_min;
+                            t_max = next_max;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn objects_lying_in_a_split_plane_stay_in_the_tree() {
+        // Two unit boxes with zero-width boxes on both of their faces, which are
+        // the only places a split can go.
+        let unit = |x: f64| Aabb::new(Vec3(x, 0.0, 0.0), Vec3(x + 1.0, 1.0, 1.0));
+        let flat = |x: f64| Aabb::new(Vec3(x, 0.0, 0.0), Vec3(x, 1.0, 1.0));
+        let boxes = vec![unit(0.0), unit(2.0), flat(1.0), flat(2.0), flat(1.0)];
+
+        let mut tree = KdTree {
+            objects: HittableList::new(),
+            bounding_box: None,
+            nodes: Vec::new(),
+            indices: Vec::new(),
+        };
+        let bounds = boxes.iter().copied().reduce(Aabb::surrounding_box).unwrap();
+        tree.build(&boxes, bounds, (0..boxes.len() as u32).collect(), 8, 0);
+
+        assert!(tree
+            .nodes
+            .iter()
+            .any(|node| matches!(node, KdNode::Interior { .. })));
+        for object in 0..boxes.len() as u32 {
+            assert!(
+                tree.indices.contains(&object),
+                "object {} was dropped",
+                object
+            );
+        }
+    }
+}
@@ -1,18 +1,30 @@
 use std::rc::Rc;
 
 use crate::material::Material;
-use crate::ray::Ray;
+use crate::packet::RayPacket;
+use crate::ray::{InvRay, Ray, RayDifferential};
 use crate::vec3::Vec3;
 
 pub mod aabb;
+pub mod bvh4;
 pub mod bvh_node;
+pub mod grid;
+pub mod kd_tree;
 pub mod movable_sphere;
+pub mod primitive;
 pub mod sphere;
 pub mod rectangle;
+pub mod triangle;
 
 use aabb::Aabb;
+pub use bvh4::Bvh4;
+use bvh_node::BvhNode;
+use grid::UniformGrid;
+use kd_tree::KdTree;
 pub use movable_sphere::MovableSphere;
+pub use primitive::{Primitive, PrimitiveBvh, PrimitiveList};
 pub use sphere::Sphere;
+pub use triangle::Triangle;
 
 #[derive(Clone)]
 pub struct HitRecord {
@@ -23,6 +35,21 @@ pub struct HitRecord {
     pub u: f64,
     pub v: f64,
     pub front_face: bool,
+    // Rates of change of `p` with `u` and `v`, which span the tangent plane. Zero
+    // if the surface didn't provide them.
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    // How far the texture coordinates move across one pixel, for hits of camera
+    // rays traced with a differential.
+    pub uv_derivatives: Option<UvDerivatives>,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct UvDerivatives {
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
 }
 
 impl HitRecord {
@@ -50,13 +77,112 @@ impl HitRecord {
             normal,
             front_face,
             mat_ptr: Rc::clone(mat_ptr),
+            dpdu: Vec3::default(),
+            dpdv: Vec3::default(),
+            uv_derivatives: None,
         }
     }
+
+    pub fn with_tangents(self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        HitRecord { dpdu, dpdv, ..self }
+    }
+
+    // Finds where the offset rays meet the tangent plane at `p`, then the changes
+    // in u and v that best account for those offsets. Needs the surface's
+    // tangents.
+    pub fn with_differential(self, differential: &RayDifferential) -> Self {
+        let plane = Vec3::dot(self.normal, self.p);
+        let offset = |ray: &Ray| {
+            let along = Vec3::dot(self.normal, ray.direction());
+            if along.abs() < 1e-12 {
+                return None;
+            }
+            let t = (plane - Vec3::dot(self.normal, ray.origin())) / along;
+            Some(ray.at(t) - self.p)
+        };
+
+        // Least squares for dp = du * dpdu + dv * dpdv.
+        let uu = Vec3::dot(self.dpdu, self.dpdu);
+        let uv = Vec3::dot(self.dpdu, self.dpdv);
+        let vv = Vec3::dot(self.dpdv, self.dpdv);
+        let determinant = uu * vv - uv * uv;
+        let solve = |dp: Vec3| {
+            let (a, b) = (Vec3::dot(self.dpdu, dp), Vec3::dot(self.dpdv, dp));
+            ((vv * a - uv * b) / determinant, (uu * b - uv * a) / determinant)
+        };
+
+        let uv_derivatives = match (offset(&differential.rx), offset(&differential.ry)) {
+            (Some(dpdx), Some(dpdy)) if determinant > 1e-12 => {
+                let (dudx, dvdx) = solve(dpdx);
+                let (dudy, dvdy) = solve(dpdy);
+                Some(UvDerivatives { dudx, dvdx, dudy, dvdy })
+            }
+            _ => None,
+        };
+
+        HitRecord { uv_derivatives, ..self }
+    }
+}
+
+// Decides whether a hit on a partially transparent surface counts, so that cutout
+// geometry lets rays through where its material is see-through. The roll comes
+// from hashing the ray and the hit point rather than a fresh random number, so
+// an accelerator that reaches the same primitive more than once gets the same
+// answer every time.
+fn alpha_test(material: &Rc<dyn Material>, ray: &Ray, u: f64, v: f64, p: Vec3) -> bool {
+    let opacity = material.opacity(u, v, p);
+    opacity >= 1.0 || (opacity > 0.0 && hash_to_unit(ray, p) < opacity)
+}
+
+// A number in [0, 1) that looks random but depends only on the ray and `p`.
+fn hash_to_unit(ray: &Ray, p: Vec3) -> f64 {
+    let (o, d) = (ray.origin(), ray.direction());
+    let words = [o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), p.x(), p.y(), p.z()];
+
+    // SplitMix64's finaliser, folded over the bits of each coordinate.
+    let hash = words.iter().fold(0x9e37_79b9_7f4a_7c15u64, |hash, word| {
+        let mut z = (hash ^ word.to_bits()).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    });
+
+    (hash >> 11) as f64 / (1u64 << 53) as f64
 }
 
 pub trait Hittable {
     fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord>;
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb>;
+
+    // `hit` for callers that already have the ray's reciprocal direction, so a
+    // tree can share one `InvRay` across all its box tests.
+    fn hit_inv(&self, ray: Ray, _inv_ray: &InvRay, min: f64, max: f64) -> Option<HitRecord> {
+        self.hit(ray, min, max)
+    }
+
+    // The shapes `PrimitiveList` stores directly return a copy of themselves.
+    fn to_primitive(&self) -> Option<Primitive> {
+        None
+    }
+
+    // Intersects every ray in the packet, replacing `records[i]` when ray `i` finds
+    // a hit closer than the one already there. Aggregates override this to cull
+    // whole subtrees at once; everything else just traces the rays one by one.
+    fn hit_packet(
+        &self,
+        packet: &RayPacket,
+        min: f64,
+        max: f64,
+        records: &mut [Option<HitRecord>],
+    ) {
+        let rays = packet.rays().iter().zip(packet.inv_rays());
+        for ((ray, inv_ray), record) in rays.zip(records.iter_mut()) {
+            let closest_so_far = record.as_ref().map_or(max, |record| record.t);
+            if let Some(hit) = self.hit_inv(*ray, inv_ray, min, closest_so_far) {
+                *record = Some(hit);
+            }
+        }
+    }
 }
 
 pub type HittableList = Vec<Rc<dyn Hittable>>;
@@ -78,6 +204,18 @@ impl Hittable for HittableList {
         hit_anything
     }
 
+    fn hit_packet(
+        &self,
+        packet: &RayPacket,
+        min: f64,
+        max: f64,
+        records: &mut [Option<HitRecord>],
+    ) {
+        for object in self {
+            object.hit_packet(packet, min, max, records);
+        }
+    }
+
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
         if self.is_empty() {
             return None;
@@ -93,3 +231,149 @@ impl Hittable for HittableList {
         Some(output_box)
     }
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Accelerator {
+    Bvh,
+    Bvh4,
+    Grid,
+    KdTree,
+}
+
+impl Accelerator {
+    pub fn build(self, objects: HittableList, time0: f64, time1: f64) -> Rc<dyn Hittable> {
+        match self {
+            Accelerator::Bvh => Rc::new(BvhNode::new(objects, time0, time1)),
+            Accelerator::Bvh4 => Rc::new(Bvh4::new(objects.into_iter().collect(), time0, time1)),
+            Accelerator::Grid => Rc::new(UniformGrid::new(objects, time0, time1)),
+            Accelerator::KdTree => Rc::new(KdTree::new(objects, time0, time1)),
+        }
+    }
+}
+
+impl std::str::FromStr for Accelerator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bvh" => Ok(Accelerator::Bvh),
+            "bvh4" => Ok(Accelerator::Bvh4),
+            "grid" => Ok(Accelerator::Grid),
+            "kd" | "kdtree" | "kd-tree" => Ok(Accelerator::KdTree),
+            _ => Err(format!(
+                "Unknown accelerator '{}', expected bvh, bvh4, grid or kdtree",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Cutout, Lambertian};
+    use crate::texture::Texture;
+    use crate::{rand_with_range, random_double};
+
+    // Spheres and triangles of mixed sizes, some overlapping, some in the same
+    // axis-aligned planes so the split planes land on their faces.
+    fn scene() -> HittableList {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::from((0.5, 0.5, 0.5)));
+        let mut objects = HittableList::new();
+
+        for i in 0..200 {
+            let center = Vec3::rand_with_range(-10.0, 10.0);
+
+            if i % 2 == 0 {
+                let radius = rand_with_range(0.05, 1.5);
+                objects.push(Rc::new(Sphere::new(center, radius, material.clone())));
+            } else {
+                let z = (center.z() / 4.0).round() * 4.0;
+                objects.push(Rc::new(Triangle::new(
+                    Vec3(center.x(), center.y(), z),
+                    Vec3(center.x() + 1.0, center.y(), z),
+                    Vec3(center.x(), center.y() + 1.0, z),
+                    material.clone(),
+                )));
+            }
+        }
+
+        objects
+    }
+
+    #[test]
+    fn every_accelerator_finds_the_same_hits_as_the_bvh() {
+        let objects = scene();
+        let bvh = Accelerator::Bvh.build(objects.clone(), 0.0, 1.0);
+        let grid = Accelerator::Grid.build(objects.clone(), 0.0, 1.0);
+        let kd_tree = Accelerator::KdTree.build(objects.clone(), 0.0, 1.0);
+        let bvh4 = Accelerator::Bvh4.build(objects, 0.0, 1.0);
+
+        for _ in 0..5000 {
+            let origin = 20.0 * Vec3::rand_unit_vector();
+            let target = Vec3::rand_with_range(-10.0, 10.0);
+            let ray = Ray::new(origin, target - origin, 0.0);
+
+            let expected = bvh.hit(ray, 0.001, f64::INFINITY).map(|record| record.t);
+            let grid_t = grid.hit(ray, 0.001, f64::INFINITY).map(|record| record.t);
+            let kd_t = kd_tree.hit(ray, 0.001, f64::INFINITY).map(|record| record.t);
+            let bvh4_t = bvh4.hit(ray, 0.001, f64::INFINITY).map(|record| record.t);
+            assert_eq!(grid_t, expected, "grid disagrees on {:?}", ray);
+            assert_eq!(kd_t, expected, "k-d tree disagrees on {:?}", ray);
+            assert_eq!(bvh4_t, expected, "Bvh4 disagrees on {:?}", ray);
+        }
+    }
+
+    // White everywhere, with the same partial alpha everywhere.
+    struct Coverage(f64);
+
+    impl Texture for Coverage {
+        fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Vec3 {
+            Vec3(1.0, 1.0, 1.0)
+        }
+
+        fn alpha(&self, _u: f64, _v: f64, _p: Vec3) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn partial_alpha_lets_through_its_share_of_rays_in_every_accelerator() {
+        let base: Rc<dyn Material> = Rc::new(Lambertian::from((0.5, 0.5, 0.5)));
+        let leaf: Rc<dyn Material> = Rc::new(Cutout::new(base, Rc::new(Coverage(0.3))));
+        let objects: HittableList = vec![Rc::new(rectangle::XYRect::new(
+            -1.0, 1.0, -1.0, 1.0, 0.0, leaf,
+        ))];
+
+        for &accelerator in &[
+            Accelerator::Bvh,
+            Accelerator::Bvh4,
+            Accelerator::Grid,
+            Accelerator::KdTree,
+        ] {
+            let world = accelerator.build(objects.clone(), 0.0, 1.0);
+            let rays = 20_000;
+            let mut hits = 0;
+
+            for _ in 0..rays {
+                let origin = Vec3(random_double(), random_double(), 5.0);
+                let target = Vec3(rand_with_range(-0.9, 0.9), rand_with_range(-0.9, 0.9), 0.0);
+                let ray = Ray::new(origin, target - origin, 0.0);
+
+                let hit = world.hit(ray, 0.001, f64::INFINITY).is_some();
+                assert_eq!(world.hit(ray, 0.001, f64::INFINITY).is_some(), hit);
+                if hit {
+                    hits += 1;
+                }
+            }
+
+            let coverage = hits as f64 / rays as f64;
+            assert!(
+                (coverage - 0.3).abs() < 0.02,
+                "{:?} covered {}",
+                accelerator,
+                coverage
+            );
+        }
+    }
+}
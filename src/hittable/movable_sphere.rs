@@ -1,9 +1,15 @@
 use std::rc::Rc;
 
-use super::{aabb::Aabb, sphere::get_sphere_uv, HitRecord, Hittable};
+use super::{
+    aabb::Aabb,
+    alpha_test,
+    sphere::{get_sphere_tangents, get_sphere_uv},
+    HitRecord, Hittable, Primitive,
+};
 use crate::vec3::Vec3;
 use crate::{material::Material, ray::Ray};
 
+#[derive(Clone)]
 pub struct MovableSphere {
     center0: Vec3,
     center1: Vec3,
@@ -55,9 +61,12 @@ impl Hittable for MovableSphere {
                 let p = ray.at(t);
                 let normal = (p - self.center(ray.time())) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray);
-
-                return Some(record);
+                if alpha_test(&self.mat_ptr, &ray, u, v, p) {
+                    let (dpdu, dpdv) = get_sphere_tangents(&normal, self.radius);
+                    let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray)
+                        .with_tangents(dpdu, dpdv);
+                    return Some(record);
+                }
             }
 
             let temp = (-half_b + root) / a;
@@ -66,9 +75,12 @@ impl Hittable for MovableSphere {
                 let p = ray.at(t);
                 let normal = (p - self.center(ray.time())) / self.radius;
                 let (u, v) = get_sphere_uv(&normal);
-                let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray);
-
-                return Some(record);
+                if alpha_test(&self.mat_ptr, &ray, u, v, p) {
+                    let (dpdu, dpdv) = get_sphere_tangents(&normal, self.radius);
+                    let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray)
+                        .with_tangents(dpdu, dpdv);
+                    return Some(record);
+                }
             }
         }
 
@@ -90,4 +102,8 @@ impl Hittable for MovableSphere {
 
         Some(output_box)
     }
+
+    fn to_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::from(self.clone()))
+    }
 }
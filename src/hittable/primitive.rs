@@ -0,0 +1,503 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{
+    aabb::Aabb,
+    rectangle::XYRect,
+    sphere::hit_sphere,
+    triangle::{hit_triangle, triangle_bounds},
+    HitRecord, Hittable, MovableSphere, Sphere, Triangle,
+};
+use crate::{
+    material::Material,
+    ray::{InvRay, Ray},
+    stats,
+    vec3::Vec3,
+};
+
+// Statically dispatched alternative to `HittableList`. Spheres and triangles are
+// stored struct-of-arrays: centres, radii and vertices each in their own array,
+// with materials held once and referenced by index, so traversal reads only the
+// geometry it tests. The rarer kinds keep one array of structs each, and any
+// other hittable is kept as a trait object. Everything is addressed through a
+// compact `PrimitiveRef`, which is what `PrimitiveBvh` stores and reorders.
+
+#[derive(Clone)]
+pub enum Primitive {
+    Sphere(Sphere),
+    MovableSphere(MovableSphere),
+    XYRect(XYRect),
+    Triangle(Triangle),
+    // Anything else, reached through the trait object as usual.
+    Other(Rc<dyn Hittable>),
+}
+
+impl Hittable for Primitive {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        match self {
+            Primitive::Sphere(sphere) => sphere.hit(ray, min, max),
+            Primitive::MovableSphere(sphere) => sphere.hit(ray, min, max),
+            Primitive::XYRect(rect) => rect.hit(ray, min, max),
+            Primitive::Triangle(triangle) => triangle.hit(ray, min, max),
+            Primitive::Other(object) => object.hit(ray, min, max),
+        }
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        match self {
+            Primitive::Sphere(sphere) => sphere.bounding_box(t0, t1),
+            Primitive::MovableSphere(sphere) => sphere.bounding_box(t0, t1),
+            Primitive::XYRect(rect) => rect.bounding_box(t0, t1),
+            Primitive::Triangle(triangle) => triangle.bounding_box(t0, t1),
+            Primitive::Other(object) => object.bounding_box(t0, t1),
+        }
+    }
+}
+
+impl From<Sphere> for Primitive {
+    fn from(sphere: Sphere) -> Self {
+        Primitive::Sphere(sphere)
+    }
+}
+
+impl From<MovableSphere> for Primitive {
+    fn from(sphere: MovableSphere) -> Self {
+        Primitive::MovableSphere(sphere)
+    }
+}
+
+impl From<XYRect> for Primitive {
+    fn from(rect: XYRect) -> Self {
+        Primitive::XYRect(rect)
+    }
+}
+
+impl From<Triangle> for Primitive {
+    fn from(triangle: Triangle) -> Self {
+        Primitive::Triangle(triangle)
+    }
+}
+
+// Unwraps the shapes `PrimitiveList` knows about, so a `HittableList` can be
+// collected into one.
+impl From<Rc<dyn Hittable>> for Primitive {
+    fn from(object: Rc<dyn Hittable>) -> Self {
+        object.to_primitive().unwrap_or(Primitive::Other(object))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum PrimitiveRef {
+    Sphere(u32),
+    MovableSphere(u32),
+    XYRect(u32),
+    Triangle(u32),
+    Other(u32),
+}
+
+#[derive(Clone, Default)]
+pub struct PrimitiveList {
+    // Spheres and triangles, the kinds scenes have by the thousand, are split
+    // into one array per field. Their materials are indices into `materials`.
+    sphere_centers: Vec<Vec3>,
+    sphere_radii: Vec<f64>,
+    sphere_materials: Vec<u32>,
+    triangle_v0: Vec<Vec3>,
+    triangle_v1: Vec<Vec3>,
+    triangle_v2: Vec<Vec3>,
+    triangle_materials: Vec<u32>,
+    movable_spheres: Vec<MovableSphere>,
+    xy_rects: Vec<XYRect>,
+    others: Vec<Rc<dyn Hittable>>,
+    // Each distinct material once, keyed by address so shared instances stay
+    // shared.
+    materials: Vec<Rc<dyn Material>>,
+    material_ids: HashMap<*const (), u32>,
+    refs: Vec<PrimitiveRef>,
+}
+
+impl PrimitiveList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push<P: Into<Primitive>>(&mut self, primitive: P) {
+        let primitive_ref = match primitive.into() {
+            Primitive::Sphere(sphere) => {
+                let (center, radius, material) = sphere.parts();
+                let material = self.material_id(material);
+                self.sphere_centers.push(center);
+                self.sphere_radii.push(radius);
+                self.sphere_materials.push(material);
+                PrimitiveRef::Sphere(self.sphere_centers.len() as u32 - 1)
+            }
+            Primitive::MovableSphere(sphere) => {
+                self.movable_spheres.push(sphere);
+                PrimitiveRef::MovableSphere(self.movable_spheres.len() as u32 - 1)
+            }
+            Primitive::XYRect(rect) => {
+                self.xy_rects.push(rect);
+                PrimitiveRef::XYRect(self.xy_rects.len() as u32 - 1)
+            }
+            Primitive::Triangle(triangle) => {
+                let (v0, v1, v2) = triangle.vertices();
+                let material = self.material_id(triangle.material());
+                self.triangle_v0.push(v0);
+                self.triangle_v1.push(v1);
+                self.triangle_v2.push(v2);
+                self.triangle_materials.push(material);
+                PrimitiveRef::Triangle(self.triangle_v0.len() as u32 - 1)
+            }
+            Primitive::Other(object) => {
+                self.others.push(object);
+                PrimitiveRef::Other(self.others.len() as u32 - 1)
+            }
+        };
+
+        self.refs.push(primitive_ref);
+    }
+
+    fn material_id(&mut self, material: &Rc<dyn Material>) -> u32 {
+        let materials = &mut self.materials;
+        *self
+            .material_ids
+            .entry(Rc::as_ptr(material) as *const ())
+            .or_insert_with(|| {
+                materials.push(material.clone());
+                materials.len() as u32 - 1
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.refs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.refs.is_empty()
+    }
+
+    pub fn refs(&self) -> &[PrimitiveRef] {
+        &self.refs
+    }
+
+    // Number of distinct materials the primitives use.
+    pub fn material_count(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn triangle_vertices(&self, index: u32) -> (Vec3, Vec3, Vec3) {
+        let i = index as usize;
+        (
+            self.triangle_v0[i],
+            self.triangle_v1[i],
+            self.triangle_v2[i],
+        )
+    }
+
+    pub fn hit_ref(
+        &self,
+        primitive: PrimitiveRef,
+        ray: Ray,
+        min: f64,
+        max: f64,
+    ) -> Option<HitRecord> {
+        match primitive {
+            PrimitiveRef::Sphere(i) => {
+                let i = i as usize;
+                let material = &self.materials[self.sphere_materials[i] as usize];
+                hit_sphere(
+                    self.sphere_centers[i],
+                    self.sphere_radii[i],
+                    material,
+                    ray,
+                    min,
+                    max,
+                )
+            }
+            PrimitiveRef::MovableSphere(i) => self.movable_spheres[i as usize].hit(ray, min, max),
+            PrimitiveRef::XYRect(i) => self.xy_rects[i as usize].hit(ray, min, max),
+            PrimitiveRef::Triangle(i) => {
+                let (v0, v1, v2) = self.triangle_vertices(i);
+                let material = &self.materials[self.triangle_materials[i as usize] as usize];
+                hit_triangle(v0, v1, v2, material, ray, min, max)
+            }
+            PrimitiveRef::Other(i) => self.others[i as usize].hit(ray, min, max),
+        }
+    }
+
+    pub fn bounding_box_ref(&self, primitive: PrimitiveRef, t0: f64, t1: f64) -> Option<Aabb> {
+        match primitive {
+            PrimitiveRef::Sphere(i) => {
+                let center = self.sphere_centers[i as usize];
+                let radius = self.sphere_radii[i as usize];
+                let extent = Vec3(radius, radius, radius);
+                Some(Aabb::new(center - extent, center + extent))
+            }
+            PrimitiveRef::MovableSphere(i) => self.movable_spheres[i as usize].bounding_box(t0, t1),
+            PrimitiveRef::XYRect(i) => self.xy_rects[i as usize].bounding_box(t0, t1),
+            PrimitiveRef::Triangle(i) => {
+                let (v0, v1, v2) = self.triangle_vertices(i);
+                Some(triangle_bounds(v0, v1, v2))
+            }
+            PrimitiveRef::Other(i) => self.others[i as usize].bounding_box(t0, t1),
+        }
+    }
+
+    // Closest hit among `refs`, which must come from this list.
+    fn hit_refs(&self, refs: &[PrimitiveRef], ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+
+        for &primitive in refs {
+            if let Some(record) = self.hit_ref(primitive, ray, min, closest_so_far) {
+                closest_so_far = record.t;
+                hit_anything = Some(record);
+            }
+        }
+
+        hit_anything
+    }
+}
+
+impl<P: Into<Primitive>> Extend<P> for PrimitiveList {
+    fn extend<I: IntoIterator<Item = P>>(&mut self, iter: I) {
+        for primitive in iter {
+            self.push(primitive);
+        }
+    }
+}
+
+impl<P: Into<Primitive>> std::iter::FromIterator<P> for PrimitiveList {
+    fn from_iter<I: IntoIterator<Item = P>>(iter: I) -> Self {
+        let mut list = PrimitiveList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl Hittable for PrimitiveList {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        stats::count_primitive_tests(self.refs.len() as u64);
+        self.hit_refs(&self.refs, ray, min, max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<Aabb> {
+        let mut refs = self.refs.iter();
+        let mut output_box = self.bounding_box_ref(*refs.next()?, t0, t1)?;
+
+        for &primitive in refs {
+            let temp_box = self.bounding_box_ref(primitive, t0, t1)?;
+            output_box = Aabb::surrounding_box(output_box, temp_box);
+        }
+
+        Some(output_box)
+    }
+}
+
+const MAX_LEAF_SIZE: usize = 4;
+
+pub(super) fn surrounding_box(bounds: &[(PrimitiveRef, Aabb)]) -> Aabb {
+    bounds
+        .iter()
+        .skip(1)
+        .fold(bounds[0].1, |acc, (_, b)| Aabb::surrounding_box(acc, *b))
+}
+
+// Sorts by centroid along the axis where the centroids are most spread out, so
+// that splitting at the median gives two compact halves. Returns that axis.
+pub(super) fn sort_along_widest_axis(bounds: &mut [(PrimitiveRef, Aabb)]) -> usize {
+    let centroid = |b: &Aabb| 0.5 * (b.min() + b.max());
+    let (lo, hi) = bounds.iter().skip(1).fold(
+        (centroid(&bounds[0].1), centroid(&bounds[0].1)),
+        |(lo, hi), (_, b)| {
+            let c = centroid(b);
+            (
+                Vec3(lo.x().min(c.x()), lo.y().min(c.y()), lo.z().min(c.z())),
+                Vec3(hi.x().max(c.x()), hi.y().max(c.y()), hi.z().max(c.z())),
+            )
+        },
+    );
+    let extent = hi - lo;
+    let axis = if extent.x() > extent.y() && extent.x() > extent.z() {
+        0
+    } else if extent.y() > extent.z() {
+        1
+    } else {
+        2
+    };
+
+    bounds.sort_by(|(_, a), (_, b)| {
+        centroid(a)
+            .get(axis)
+            .partial_cmp(&centroid(b).get(axis))
+            .unwrap()
+    });
+
+    axis
+}
+
+// A node is a leaf when `count > 0`, covering `refs[offset..offset + count]`.
+// Otherwise its left child directly follows it and `offset` is the right child.
+#[derive(Debug, Copy, Clone)]
+struct FlatNode {
+    bounding_box: Aabb,
+    offset: u32,
+    count: u16,
+    axis: u8,
+}
+
+/// A flattened BVH that intersects a `PrimitiveList` without going through
+/// `dyn Hittable` anywhere on the traversal path.
+pub struct PrimitiveBvh {
+    primitives: PrimitiveList,
+    nodes: Vec<FlatNode>,
+}
+
+impl PrimitiveBvh {
+    pub fn new(mut primitives: PrimitiveList, time0: f64, time1: f64) -> Self {
+        let mut refs = std::mem::take(&mut primitives.refs);
+        let mut nodes = Vec::with_capacity(2 * refs.len());
+
+        if !refs.is_empty() {
+            let mut bounds: Vec<(PrimitiveRef, Aabb)> = refs
+                .iter()
+                .map(|&primitive| {
+                    let bounding_box = primitives
+                        .bounding_box_ref(primitive, time0, time1)
+                        .expect("No bounding box in PrimitiveBvh constructor.");
+                    (primitive, bounding_box)
+                })
+                .collect();
+
+            Self::build(&mut bounds, 0, &mut nodes);
+            refs = bounds.into_iter().map(|(primitive, _)| primitive).collect();
+        }
+
+        primitives.refs = refs;
+
+        Self { primitives, nodes }
+    }
+
+    fn build(bounds: &mut [(PrimitiveRef, Aabb)], offset: usize, nodes: &mut Vec<FlatNode>) {
+        let bounding_box = surrounding_box(bounds);
+        let index = nodes.len();
+
+        if bounds.len() <= MAX_LEAF_SIZE {
+            nodes.push(FlatNode {
+                bounding_box,
+                offset: offset as u32,
+                count: bounds.len() as u16,
+                axis: 0,
+            });
+            return;
+        }
+
+        let axis = sort_along_widest_axis(bounds);
+
+        nodes.push(FlatNode {
+            bounding_box,
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+        });
+
+        let mid = bounds.len() / 2;
+        let (left, right) = bounds.split_at_mut(mid);
+        Self::build(left, offset, nodes);
+        nodes[index].offset = nodes.len() as u32;
+        Self::build(right, offset + mid, nodes);
+    }
+
+    pub fn primitives(&self) -> &PrimitiveList {
+        &self.primitives
+    }
+}
+
+impl Hittable for PrimitiveBvh {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_ray = InvRay::from(&ray);
+        let mut hit_anything = None;
+        let mut closest_so_far = max;
+        let mut stack = [0usize; 64];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len]];
+
+            if !node.bounding_box.hit_inv(&inv_ray, min, closest_so_far) {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.offset as usize;
+                let refs = &self.primitives.refs[start..start + node.count as usize];
+                stats::count_primitive_tests(refs.len() as u64);
+
+                if let Some(record) = self.primitives.hit_refs(refs, ray, min, closest_so_far) {
+                    closest_so_far = record.t;
+                    hit_anything = Some(record);
+                }
+                continue;
+            }
+
+            // Push the far child first so the near one is visited next.
+            let left = stack[stack_len] + 1;
+            let right = node.offset as usize;
+            if ray.direction().get(node.axis as usize) < 0.0 {
+                stack[stack_len] = left;
+                stack[stack_len + 1] = right;
+            } else {
+                stack[stack_len] = right;
+                stack[stack_len + 1] = left;
+            }
+            stack_len += 2;
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bounding_box)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    #[test]
+    fn shared_materials_are_stored_once_and_hits_match_the_structs() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::from((0.5, 0.5, 0.5)));
+        let sphere = Sphere::new(Vec3(0.0, 0.0, -2.0), 0.5, material.clone());
+        let triangle = Triangle::new(
+            Vec3(-1.0, -1.0, -4.0),
+            Vec3(1.0, -1.0, -4.0),
+            Vec3(0.0, 1.0, -4.0),
+            material,
+        );
+
+        let mut list = PrimitiveList::new();
+        list.push(sphere.clone());
+        list.push(triangle.clone());
+        assert_eq!(list.material_count(), 1);
+
+        let ray = Ray::new(Vec3(0.0, 0.0, 0.0), Vec3(0.0, 0.0, -1.0), 0.0);
+        let expected = sphere.hit(ray, 0.001, f64::MAX).unwrap();
+        let record = list.hit(ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(record.t, expected.t);
+        assert_eq!(record.p.z(), expected.p.z());
+
+        let ray = Ray::new(Vec3(0.2, 0.0, 0.0), Vec3(0.0, 0.0, -1.0), 0.0);
+        let expected = triangle.hit(ray, 0.001, f64::MAX).unwrap();
+        let record = list.hit_ref(list.refs()[1], ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(
+            (record.t, record.u, record.v),
+            (expected.t, expected.u, expected.v)
+        );
+    }
+}
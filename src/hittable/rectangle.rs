@@ -1,5 +1,6 @@
 use super::*;
 
+#[derive(Clone)]
 pub struct XYRect {
     x0: f64,
     x1: f64,
@@ -35,7 +36,12 @@ impl Hittable for XYRect {
 
         let p = ray.at(t);
 
-        let record = HitRecord::new(p, t, u, v, outward_normal, &self.mat_ptr, &ray);
+        if !alpha_test(&self.mat_ptr, &ray, u, v, p) {
+            return None;
+        }
+
+        let record = HitRecord::new(p, t, u, v, outward_normal, &self.mat_ptr, &ray)
+            .with_tangents(Vec3(self.x1 - self.x0, 0.0, 0.0), Vec3(0.0, self.y1 - self.y0, 0.0));
 
         Some(record)
     }
@@ -44,5 +50,9 @@ impl Hittable for XYRect {
         // dimension a small amount.
         Some(aabb::Aabb::new(Vec3(self.x0, self.y0, self.k-0.0001), Vec3(self.x1, self.y1, self.k+0.0001)))
     }
+
+    fn to_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::from(self.clone()))
+    }
     
 }
\ No newline at end of file
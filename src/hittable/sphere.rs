@@ -1,6 +1,6 @@
 use std::{f64::consts::PI, rc::Rc};
 
-use super::{aabb::Aabb, HitRecord, Hittable};
+use super::{aabb::Aabb, alpha_test, HitRecord, Hittable, Primitive};
 use crate::vec3::Vec3;
 use crate::{material::Material, ray::Ray};
 
@@ -19,43 +19,15 @@ impl Sphere {
             mat_ptr,
         }
     }
+
+    pub(super) fn parts(&self) -> (Vec3, f64, &Rc<dyn Material>) {
+        (self.center, self.radius, &self.mat_ptr)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
-        let oc = ray.origin() - self.center;
-        let a = Vec3::dot(ray.direction(), ray.direction());
-        let half_b = Vec3::dot(oc, ray.direction());
-        let c = Vec3::dot(oc, oc) - self.radius * self.radius;
-        let discriminant = half_b * half_b - a * c;
-
-        if discriminant > 0.0 {
-            let root = discriminant.sqrt();
-            let temp = (-half_b - root) / a;
-
-            if temp < max && temp > min {
-                let t = temp;
-                let p = ray.at(t);
-                let normal = (p - self.center) / self.radius;
-                let (u, v) = get_sphere_uv(&normal);
-                let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray);
-
-                return Some(record);
-            }
-
-            let temp = (-half_b + root) / a;
-            if temp < max && temp > min {
-                let t = temp;
-                let p = ray.at(t);
-                let normal = (p - self.center) / self.radius;
-                let (u, v) = get_sphere_uv(&normal);
-                let record = HitRecord::new(p, t, u, v, normal, &self.mat_ptr, &ray);
-
-                return Some(record);
-            }
-        }
-
-        None
+        hit_sphere(self.center, self.radius, &self.mat_ptr, ray, min, max)
     }
 
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
@@ -65,6 +37,60 @@ impl Hittable for Sphere {
         );
         Some(output_box)
     }
+
+    fn to_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::from(self.clone()))
+    }
+}
+
+// Shared by `Sphere` and the sphere arrays in `PrimitiveList`.
+pub(super) fn hit_sphere(
+    center: Vec3,
+    radius: f64,
+    mat_ptr: &Rc<dyn Material>,
+    ray: Ray,
+    min: f64,
+    max: f64,
+) -> Option<HitRecord> {
+    let oc = ray.origin() - center;
+    let a = Vec3::dot(ray.direction(), ray.direction());
+    let half_b = Vec3::dot(oc, ray.direction());
+    let c = Vec3::dot(oc, oc) - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+
+    if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        let temp = (-half_b - root) / a;
+
+        if temp < max && temp > min {
+            let t = temp;
+            let p = ray.at(t);
+            let normal = (p - center) / radius;
+            let (u, v) = get_sphere_uv(&normal);
+            if alpha_test(mat_ptr, &ray, u, v, p) {
+                let (dpdu, dpdv) = get_sphere_tangents(&normal, radius);
+                let record =
+                    HitRecord::new(p, t, u, v, normal, mat_ptr, &ray).with_tangents(dpdu, dpdv);
+                return Some(record);
+            }
+        }
+
+        let temp = (-half_b + root) / a;
+        if temp < max && temp > min {
+            let t = temp;
+            let p = ray.at(t);
+            let normal = (p - center) / radius;
+            let (u, v) = get_sphere_uv(&normal);
+            if alpha_test(mat_ptr, &ray, u, v, p) {
+                let (dpdu, dpdv) = get_sphere_tangents(&normal, radius);
+                let record =
+                    HitRecord::new(p, t, u, v, normal, mat_ptr, &ray).with_tangents(dpdu, dpdv);
+                return Some(record);
+            }
+        }
+    }
+
+    None
 }
 
 pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
@@ -74,3 +100,29 @@ pub fn get_sphere_uv(p: &Vec3) -> (f64, f64) {
     let v = (theta + PI / 2.0) / PI;
     (u, v)
 }
+
+// The derivatives of the point on the sphere with respect to the (u, v) from
+// `get_sphere_uv`, for a unit normal `p`.
+pub fn get_sphere_tangents(p: &Vec3, radius: f64) -> (Vec3, Vec3) {
+    let cos_theta = (p.x() * p.x() + p.z() * p.z()).sqrt();
+    let dpdu = 2.0 * PI * radius * Vec3(p.z(), 0.0, -p.x());
+
+    // At the poles the parallels shrink to a point; any horizontal direction
+    // will do.
+    if cos_theta < 1e-8 {
+        return (
+            Vec3(0.0, 0.0, 2.0 * PI * radius),
+            Vec3(PI * radius, 0.0, 0.0),
+        );
+    }
+
+    let dpdv = PI
+        * radius
+        * Vec3(
+            -p.y() * p.x() / cos_theta,
+            cos_theta,
+            -p.y() * p.z() / cos_theta,
+        );
+
+    (dpdu, dpdv)
+}
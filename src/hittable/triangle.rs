@@ -0,0 +1,116 @@
+use std::rc::Rc;
+
+use super::{aabb::Aabb, alpha_test, HitRecord, Hittable, Primitive};
+use crate::vec3::Vec3;
+use crate::{material::Material, ray::Ray};
+
+#[derive(Clone)]
+pub struct Triangle {
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    mat_ptr: Rc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, mat_ptr: Rc<dyn Material>) -> Self {
+        Triangle {
+            v0,
+            v1,
+            v2,
+            mat_ptr,
+        }
+    }
+
+    pub fn vertices(&self) -> (Vec3, Vec3, Vec3) {
+        (self.v0, self.v1, self.v2)
+    }
+
+    pub(super) fn material(&self) -> &Rc<dyn Material> {
+        &self.mat_ptr
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: Ray, min: f64, max: f64) -> Option<HitRecord> {
+        hit_triangle(self.v0, self.v1, self.v2, &self.mat_ptr, ray, min, max)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<Aabb> {
+        Some(triangle_bounds(self.v0, self.v1, self.v2))
+    }
+
+    fn to_primitive(&self) -> Option<Primitive> {
+        Some(Primitive::from(self.clone()))
+    }
+}
+
+// Möller–Trumbore; (u, v) are the barycentric coordinates of the hit.
+pub(super) fn hit_triangle(
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    mat_ptr: &Rc<dyn Material>,
+    ray: Ray,
+    min: f64,
+    max: f64,
+) -> Option<HitRecord> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    let pvec = Vec3::cross(ray.direction(), edge2);
+    let det = Vec3::dot(edge1, pvec);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = ray.origin() - v0;
+
+    let u = Vec3::dot(tvec, pvec) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let qvec = Vec3::cross(tvec, edge1);
+    let v = Vec3::dot(ray.direction(), qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(edge2, qvec) * inv_det;
+    if t < min || t > max {
+        return None;
+    }
+
+    let p = ray.at(t);
+    if !alpha_test(mat_ptr, &ray, u, v, p) {
+        return None;
+    }
+
+    let outward_normal = Vec3::unit_vector(Vec3::cross(edge1, edge2));
+    let record =
+        HitRecord::new(p, t, u, v, outward_normal, mat_ptr, &ray).with_tangents(edge1, edge2);
+
+    Some(record)
+}
+
+pub(super) fn triangle_bounds(v0: Vec3, v1: Vec3, v2: Vec3) -> Aabb {
+    let min = Vec3(
+        f64::min(v0.x(), f64::min(v1.x(), v2.x())),
+        f64::min(v0.y(), f64::min(v1.y(), v2.y())),
+        f64::min(v0.z(), f64::min(v1.z(), v2.z())),
+    );
+
+    let max = Vec3(
+        f64::max(v0.x(), f64::max(v1.x(), v2.x())),
+        f64::max(v0.y(), f64::max(v1.y(), v2.y())),
+        f64::max(v0.z(), f64::max(v1.z(), v2.z())),
+    );
+
+    // Axis-aligned triangles are flat in one dimension, so pad every side a
+    // small amount like the rectangles do.
+    let padding = Vec3(0.0001, 0.0001, 0.0001);
+    Aabb::new(min - padding, max + padding)
+}
@@ -0,0 +1,321 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+#[derive(Debug)]
+pub enum IesError {
+    Io(io::Error),
+    UnexpectedEnd,
+    InvalidNumber(String),
+    MissingTilt,
+    // TILT=<file> points at a separate tilt table, which isn't read.
+    UnsupportedTilt(String),
+    // Only type C photometry, which nearly all architectural fixtures use, is
+    // supported.
+    UnsupportedPhotometricType(u32),
+    InvalidAngles,
+}
+
+impl fmt::Display for IesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IesError::Io(error) => write!(f, "could not read IES file: {}", error),
+            IesError::UnexpectedEnd => write!(f, "IES data ended early"),
+            IesError::InvalidNumber(token) => write!(f, "invalid number in IES data: {:?}", token),
+            IesError::MissingTilt => write!(f, "IES file has no TILT line"),
+            IesError::UnsupportedTilt(tilt) => write!(f, "unsupported IES tilt {:?}", tilt),
+            IesError::UnsupportedPhotometricType(kind) => {
+                write!(f, "unsupported IES photometric type {}", kind)
+            }
+            IesError::InvalidAngles => write!(f, "IES angles are empty or out of order"),
+        }
+    }
+}
+
+impl Error for IesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IesError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IesError {
+    fn from(error: io::Error) -> Self {
+        IesError::Io(error)
+    }
+}
+
+const PHOTOMETRIC_TYPE_C: u32 = 1;
+
+/// Luminous intensity by direction from an IES LM-63 file.
+///
+/// Angles are in degrees. The vertical angle is measured from straight down out
+/// of the fixture, and the horizontal angle around that axis.
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+    vertical_angles: Vec<f64>,
+    horizontal_angles: Vec<f64>,
+    // Candela, one row of `vertical_angles.len()` values per horizontal angle.
+    candela: Vec<f64>,
+    max_candela: f64,
+}
+
+impl IesProfile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, IesError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, IesError> {
+        // Everything before TILT is a version line and free-form keywords.
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => break line.trim(),
+                Some(_) => continue,
+                None => return Err(IesError::MissingTilt),
+            }
+        };
+
+        let rest: Vec<&str> = lines.collect();
+        let mut numbers = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<f64>()
+                    .map_err(|_| IesError::InvalidNumber(token.to_string()))
+            });
+        let mut next = || numbers.next().unwrap_or(Err(IesError::UnexpectedEnd));
+
+        // Lamp tilt only matters for fixtures aimed off vertical, so its table is
+        // skipped rather than applied.
+        match tilt {
+            "TILT=NONE" => {}
+            "TILT=INCLUDE" => {
+                let _geometry = next()?;
+                let pairs = next()? as usize;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            _ => return Err(IesError::UnsupportedTilt(tilt.to_string())),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()? as u32;
+        let _units = next()?;
+        let _width = next()?;
+        let _length = next()?;
+        let _height = next()?;
+        let ballast_factor = next()?;
+        let _future_use = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != PHOTOMETRIC_TYPE_C {
+            return Err(IesError::UnsupportedPhotometricType(photometric_type));
+        }
+
+        let mut read = |count: usize| (0..count).map(|_| next()).collect::<Result<Vec<_>, _>>();
+        let vertical_angles = read(vertical_count)?;
+        let horizontal_angles = read(horizontal_count)?;
+        let candela: Vec<f64> = read(vertical_count * horizontal_count)?
+            .into_iter()
+            .map(|value| value * multiplier * ballast_factor)
+            .collect();
+
+        let ascending = |angles: &[f64]| angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if vertical_angles.is_empty()
+            || horizontal_angles.is_empty()
+            || !ascending(&vertical_angles)
+            || !ascending(&horizontal_angles)
+        {
+            return Err(IesError::InvalidAngles);
+        }
+
+        let max_candela = candela.iter().cloned().fold(0.0, f64::max);
+
+        Ok(IesProfile {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            max_candela,
+        })
+    }
+
+    pub fn max_candela(&self) -> f64 {
+        self.max_candela
+    }
+
+    /// Intensity in candela, interpolated from the table. Directions outside the
+    /// measured vertical range are dark.
+    pub fn candela(&self, vertical: f64, horizontal: f64) -> f64 {
+        let (v, v_t) = match locate(&self.vertical_angles, vertical) {
+            Some(found) => found,
+            None => return 0.0,
+        };
+        let horizontal = self.fold_horizontal(horizontal);
+        let last_row = self.horizontal_angles.len() - 1;
+        let (h, next_h, h_t) = match locate(&self.horizontal_angles, horizontal) {
+            Some((h, h_t)) => (h, h + 1, h_t),
+            // A full circle that stops short of 360 degrees closes back on its
+            // first row, which is also the row at 360.
+            None if self.is_full_circle() => {
+                let last = self.horizontal_angles[last_row];
+                (last_row, 0, (horizontal - last) / (360.0 - last))
+            }
+            None => (last_row, last_row, 0.0),
+        };
+
+        let columns = self.vertical_angles.len();
+        let value = |h: usize, v: usize| {
+            let h = h.min(self.horizontal_angles.len() - 1);
+            let v = v.min(columns - 1);
+            self.candela[h * columns + v]
+        };
+
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        lerp(
+            lerp(value(h, v), value(h, v + 1), v_t),
+            lerp(value(next_h, v), value(next_h, v + 1), v_t),
+            h_t,
+        )
+    }
+
+    // Whether the table covers every horizontal angle without any symmetry, so
+    // that the last row is followed by the first.
+    fn is_full_circle(&self) -> bool {
+        let first = self.horizontal_angles[0];
+        let last = *self.horizontal_angles.last().unwrap();
+        first == 0.0 && last > 180.0 && last < 360.0
+    }
+
+    // Maps an angle in [0, 360) onto the range covered by the table, using the
+    // symmetry implied by the first and last horizontal angles.
+    fn fold_horizontal(&self, horizontal: f64) -> f64 {
+        let h = horizontal.rem_euclid(360.0);
+        let first = self.horizontal_angles[0];
+        let last = *self.horizontal_angles.last().unwrap();
+
+        if last <= 0.0 {
+            0.0
+        } else if first >= 90.0 && last <= 270.0 {
+            // Symmetric about the 90-270 degree plane, which mirrors h onto 180 - h.
+            if h < 90.0 {
+                180.0 - h
+            } else if h > 270.0 {
+                540.0 - h
+            } else {
+                h
+            }
+        } else if last <= 90.0 {
+            let h = h % 180.0;
+            if h > 90.0 {
+                180.0 - h
+            } else {
+                h
+            }
+        } else if last <= 180.0 && h > 180.0 {
+            360.0 - h
+        } else {
+            h
+        }
+    }
+}
+
+// Finds `i` and `t` such that `x` is `t` of the way from `angles[i]` to
+// `angles[i + 1]`, or `None` if it is out of range.
+fn locate(angles: &[f64], x: f64) -> Option<(usize, f64)> {
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+    if x < first || x > last {
+        return None;
+    }
+    if angles.len() == 1 {
+        return Some((0, 0.0));
+    }
+
+    let i = angles
+        .iter()
+        .rposition(|&angle| angle <= x)?
+        .min(angles.len() - 2);
+    let t = (x - angles[i]) / (angles[i + 1] - angles[i]);
+    Some((i, t.min(1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bilaterally symmetric type C fixture measured over 90-270 degrees, which
+    // is brighter towards 270 than towards 90.
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] minimal
+[MANUFAC] none
+TILT=NONE
+1 1000 2 3 3 1 2 0.5 0.5 0
+1 1 100
+0 45 90
+90 180 270
+100 50 0
+200, 100, 0
+300 150 0
+";
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn parses_a_type_c_profile() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+        assert_eq!(profile.vertical_angles, [0.0, 45.0, 90.0]);
+        assert_eq!(profile.horizontal_angles, [90.0, 180.0, 270.0]);
+        // The multiplier of 2 scales every value.
+        assert_close(profile.max_candela(), 600.0);
+        assert_close(profile.candela(0.0, 180.0), 400.0);
+        assert_close(profile.candela(22.5, 270.0), 450.0);
+        assert_close(profile.candela(135.0, 180.0), 0.0);
+    }
+
+    #[test]
+    fn mirrors_a_90_to_270_table_across_its_plane() {
+        let profile = IesProfile::parse(PROFILE).unwrap();
+        assert_close(profile.candela(0.0, 0.0), profile.candela(0.0, 180.0));
+        assert_close(profile.candela(0.0, 45.0), profile.candela(0.0, 135.0));
+        assert_close(profile.candela(0.0, 315.0), profile.candela(0.0, 225.0));
+        assert_close(profile.candela(0.0, 315.0), 500.0);
+    }
+
+    #[test]
+    fn wraps_a_full_table_that_stops_short_of_360() {
+        let text = PROFILE
+            .replace("1 1000 2 3 3 1", "1 1000 2 3 4 1")
+            .replace("90 180 270\n", "0 90 180 270\n400 200 0\n");
+        let profile = IesProfile::parse(&text).unwrap();
+        assert_eq!(profile.horizontal_angles, [0.0, 90.0, 180.0, 270.0]);
+
+        // Halfway from 270 degrees (600 cd) back round to 0 (800 cd).
+        assert_close(profile.candela(0.0, 315.0), 700.0);
+        assert_close(profile.candela(0.0, -45.0), 700.0);
+        assert_close(profile.candela(0.0, 337.5), 750.0);
+    }
+
+    #[test]
+    fn rejects_tilt_tables_in_separate_files() {
+        let text = PROFILE.replace("TILT=NONE", "TILT=lamp.tlt");
+        match IesProfile::parse(&text) {
+            Err(IesError::UnsupportedTilt(tilt)) => assert_eq!(tilt, "TILT=lamp.tlt"),
+            other => panic!("expected an unsupported tilt, got {:?}", other),
+        }
+    }
+}
@@ -3,17 +3,24 @@ pub use std::f64::consts::PI;
 use rand::prelude::*;
 use std::rc::Rc;
 
-use hittable::{bvh_node::BvhNode, HittableList, Sphere, rectangle::XYRect};
+use hittable::{rectangle::XYRect, Accelerator, HittableList, Sphere};
 use material::*;
 use texture::NoiseTexture;
 use vec3::Vec3;
 
 pub mod camera;
 pub mod hittable;
+pub mod ies;
 pub mod material;
+pub mod microfacet;
 pub mod noise;
+pub mod onb;
+pub mod packet;
 pub mod ray;
+pub mod simd;
+pub mod stats;
 pub mod texture;
+pub mod toon;
 pub mod vec3;
 
 pub fn degrees_to_radians(degrees: f64) -> f64 {
@@ -40,15 +47,33 @@ pub fn clamp(x: f64, min: f64, max: f64) -> f64 {
     }
 }
 
-pub fn random_scene() -> HittableList {
-    let mut scene: HittableList = Vec::new();
+// The scenes below take their named materials from `registry`, so scenes built
+// from the same registry share them, and overriding a name there changes every
+// scene that uses it.
+
+pub fn random_scene(registry: &mut MaterialRegistry) -> Result<HittableList, RegistryError> {
+    random_scene_with(registry, Accelerator::Bvh)
+}
+
+pub fn random_scene_with(
+    registry: &mut MaterialRegistry,
+    accelerator: Accelerator,
+) -> Result<HittableList, RegistryError> {
+    let scene = accelerator.build(random_scene_objects(registry)?, 0.0, 1.0);
+
+    Ok(vec![scene])
+}
 
-    let ground_material = Rc::new(Lambertian::from((Vec3(0.2, 0.3, 0.1), Vec3(0.9, 0.9, 0.9))));
+pub fn random_scene_objects(
+    registry: &mut MaterialRegistry,
+) -> Result<HittableList, RegistryError> {
+    let mut scene: HittableList = Vec::new();
+    let glass = registry.get("glass")?;
 
     scene.push(Rc::new(Sphere::new(
         Vec3(0.0, -1000.0, 0.0),
         1000.0,
-        ground_material,
+        registry.get("checker")?,
     )));
 
     for a in -11..11 {
@@ -60,6 +85,8 @@ pub fn random_scene() -> HittableList {
                 b as f64 + 0.9 * random_double(),
             );
 
+            // Each small sphere gets a colour of its own, so there's nothing to
+            // share for those but the glass.
             if (center - Vec3(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.75 {
                     let albedo = Vec3::random() * Vec3::random();
@@ -72,29 +99,25 @@ pub fn random_scene() -> HittableList {
                 //     scene.push(Rc::new(MovableSphere::new(center, center2, 0.0, 1.0, 0.2, mat_ptr)));
                 } else if choose_mat < 0.95 {
                     let color = Vec3::rand_with_range(0.5, 1.0);
-                    let fuzz = rand_with_range(0.0, 0.5);
-                    let mat_ptr = Rc::new(Metal::new(color, fuzz));
+                    let roughness = rand_with_range(0.0, 0.5);
+                    let mat_ptr = Rc::new(Metal::rough(color, roughness));
                     scene.push(Rc::new(Sphere::new(center, 0.2, mat_ptr)));
                 } else {
-                    let mat_ptr = Rc::new(Dielectric::new(1.5));
-                    scene.push(Rc::new(Sphere::new(center, 0.2, mat_ptr)));
+                    scene.push(Rc::new(Sphere::new(center, 0.2, glass.clone())));
                 }
             }
         }
     }
 
-    let mat_ptr = Rc::new(Dielectric::new(1.5));
-    scene.push(Rc::new(Sphere::new(Vec3(0.0, 1.0, 0.0), 1.0, mat_ptr)));
+    scene.push(Rc::new(Sphere::new(Vec3(0.0, 1.0, 0.0), 1.0, glass)));
 
-    let mat_ptr = Rc::new(Lambertian::from((0.4, 0.2, 0.1)));
+    let mat_ptr = registry.get("clay")?;
     scene.push(Rc::new(Sphere::new(Vec3(-4.0, 1.0, 0.0), 1.0, mat_ptr)));
 
-    let mat_ptr = Rc::new(Metal::new(Vec3(0.7, 0.6, 0.5), 0.0));
+    let mat_ptr = registry.get("bronze")?;
     scene.push(Rc::new(Sphere::new(Vec3(4.0, 1.0, 0.0), 1.0, mat_ptr)));
 
-    let scene = Rc::new(BvhNode::new(scene, 0.0, 1.0));
-
-    vec![scene]
+    Ok(scene)
 }
 
 pub fn two_perlin_spheres() -> HittableList {
@@ -117,16 +140,24 @@ pub fn two_perlin_spheres() -> HittableList {
     objects
 }
 
-pub fn earth() -> HittableList {
-    let earth_texture = Rc::new(Lambertian::from(
-        "F:\\workspace\\rust\\raytracer\\res\\earthmap.jpg",
-    ));
+pub fn earth(registry: &mut MaterialRegistry) -> Result<HittableList, RegistryError> {
+    let earth_texture = registry.get("earth")?;
     let globe = Rc::new(Sphere::new(Vec3(0.0, 0.0, 0.0), 2.0, earth_texture));
 
-    vec![globe]
+    Ok(vec![globe])
+}
+
+pub fn simple_light(registry: &mut MaterialRegistry) -> Result<HittableList, RegistryError> {
+    let pertext = Rc::new(NoiseTexture::new(4.0));
+    simple_light_with(registry, Rc::new(Lambertian::new(pertext)))
 }
 
-pub fn simple_light() -> HittableList {
+// `simple_light` with `subject` on the sphere in the middle, for looking at a
+// single material.
+pub fn simple_light_with(
+    registry: &mut MaterialRegistry,
+    subject: Rc<dyn Material>,
+) -> Result<HittableList, RegistryError> {
     let mut objects = HittableList::new();
 
     let pertext = Rc::new(NoiseTexture::new(4.0));
@@ -134,16 +165,12 @@ pub fn simple_light() -> HittableList {
     objects.push(Rc::new(Sphere::new(
         Vec3(0.0, -1000.0, 0.0),
         1000.0,
-        Rc::new(Lambertian::new(pertext.clone())),
-    )));
-
-    objects.push(Rc::new(Sphere::new(
-        Vec3(0.0, 2.0, 0.0),
-        2.0,
         Rc::new(Lambertian::new(pertext)),
     )));
 
-    let diffuse_light = Rc::new(DiffuseLight::from((4.0, 4.0, 4.0)));
+    objects.push(Rc::new(Sphere::new(Vec3(0.0, 2.0, 0.0), 2.0, subject)));
+
+    let diffuse_light = registry.get("light")?;
 
     objects.push(Rc::new(Sphere::new(
         Vec3(0.0, 7.0, 0.0),
@@ -157,5 +184,5 @@ pub fn simple_light() -> HittableList {
         diffuse_light
     )));
 
-    objects
+    Ok(objects)
 }
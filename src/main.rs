@@ -1,11 +1,20 @@
 use std::error::Error;
 
 use camera::Camera;
-use hittable::Hittable;
+use hittable::{bvh_node::BvhNode, Accelerator, HitRecord, Hittable};
+use image::RgbImage;
+use material::MaterialRegistry;
 use ray::Ray;
 use raytracer::*;
+use toon::{OutlinePass, Surface};
 use vec3::{Color, Vec3};
 
+const TILE_SIZE: u32 = 8;
+// Paths carry on past `max_depth` by Russian roulette rather than being cut off,
+// so long walks like subsurface scattering aren't darkened. This bounds how many
+// more bounces that can add.
+const MAX_ROULETTE_BOUNCES: i32 = 512;
+
 fn write_color(color: Vec3, samples_per_pixel: i32) -> (u8, u8, u8) {
     let mut r = color.x();
     let mut g = color.y();
@@ -24,26 +33,146 @@ fn write_color(color: Vec3, samples_per_pixel: i32) -> (u8, u8, u8) {
 }
 
 fn ray_color<T: Hittable>(ray: Ray, background: Color, world: &T, depth: i32) -> Vec3 {
-    if depth <= 0 {
+    if depth <= -MAX_ROULETTE_BOUNCES {
         return Vec3::new(0.0, 0.0, 0.0);
     }
 
-    if let Some(record) = world.hit(ray, 0.001, f64::MAX) {
-        let mut scattered = Default::default();
-        let mut attenuation = Default::default();
-        let emitted = record.mat_ptr.emitted(record.u, record.v, record.p);
+    match world.hit(ray, 0.001, f64::MAX) {
+        Some(record) => shade(ray, &record, background, world, depth),
+        None => background,
+    }
+}
 
-        if record
-            .mat_ptr
-            .scatter(&ray, &record, &mut attenuation, &mut scattered)
-        {
-            return emitted + attenuation * ray_color(scattered, background, world, depth - 1);
-        } else {
+fn shade<T: Hittable>(
+    ray: Ray,
+    record: &HitRecord,
+    background: Color,
+    world: &T,
+    depth: i32,
+) -> Vec3 {
+    let mut scattered = Default::default();
+    let mut attenuation = Default::default();
+    let emitted = record.mat_ptr.emitted(&ray, record);
+
+    if !record
+        .mat_ptr
+        .scatter(&ray, record, &mut attenuation, &mut scattered)
+    {
+        return emitted;
+    }
+
+    // Once the bounce budget is spent, survive in proportion to the brightest
+    // channel of the attenuation and scale up the survivors to make up for it.
+    if depth <= 0 {
+        let survival = attenuation
+            .x()
+            .max(attenuation.y())
+            .max(attenuation.z())
+            .min(0.95);
+        if random_double() >= survival {
             return emitted;
         }
+        attenuation /= survival;
+    }
+
+    emitted + attenuation * ray_color(scattered, background, world, depth - 1)
+}
+
+// Debug render: one ray through the centre of each pixel, coloured by how many box
+// and primitive tests it took to resolve.
+fn render_heatmap<T: Hittable + ?Sized>(
+    world: &T,
+    camera: &Camera,
+    width: u32,
+    height: u32,
+) -> RgbImage {
+    let mut costs = Vec::with_capacity((width * height) as usize);
+    let mut max_cost = 0;
+
+    for y in 0..height {
+        for x in 0..width {
+            let u = (x as f64 + 0.5) / width as f64;
+            let v = 1.0 - (y as f64 + 0.5) / height as f64;
+
+            stats::reset_counts();
+            world.hit(camera.get_ray(u, v), 0.001, f64::MAX);
+            let cost = stats::take_counts().total();
+
+            max_cost = u64::max(max_cost, cost);
+            costs.push(cost);
+        }
+    }
+
+    println!("Most tests for a single ray: {}", max_cost);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let cost = costs[(y * width + x) as usize];
+        let color = stats::heatmap_color(cost as f64, max_cost as f64);
+        image::Rgb([
+            (255.0 * color.x()) as u8,
+            (255.0 * color.y()) as u8,
+            (255.0 * color.z()) as u8,
+        ])
+    })
+}
+
+// Stylised render: flat toon shading from `toon::shade`, without bounces, then
+// ink along the edges the outline pass finds in the centre rays' hits.
+fn render_toon<T: Hittable + ?Sized>(
+    world: &T,
+    camera: &Camera,
+    width: u32,
+    height: u32,
+    samples_per_pixel: i32,
+    background: Color,
+) -> RgbImage {
+    let outline = OutlinePass::default();
+    let (du, dv) = (1.0 / width as f64, 1.0 / height as f64);
+    let mut surfaces = Vec::with_capacity((width * height) as usize);
+    let mut colors = Vec::with_capacity((width * height) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            // The first sample goes through the centre of the pixel, and its hit
+            // is the one the outline pass looks at.
+            let mut surface = None;
+            let mut color = Vec3::default();
+            for sample in 0..samples_per_pixel {
+                let (jitter_x, jitter_y) = if sample == 0 {
+                    (0.5, 0.5)
+                } else {
+                    (random_double(), random_double())
+                };
+                let u = (x as f64 + jitter_x) * du;
+                let v = 1.0 - (y as f64 + jitter_y) * dv;
+                let ray = camera.get_ray(u, v);
+                color += match world.hit(ray, 0.001, f64::MAX) {
+                    Some(record) => {
+                        if sample == 0 {
+                            surface = Some(Surface::new(&ray, &record));
+                        }
+                        let record = record.with_differential(&camera.differential(&ray, du, dv));
+                        toon::shade(&ray, &record)
+                    }
+                    None => background,
+                };
+            }
+            surfaces.push(surface);
+            colors.push(color);
+        }
     }
 
-    background
+    let edges = outline.edges(&surfaces, width, height);
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let i = (y * width + x) as usize;
+        let (r, g, b) = if edges[i] {
+            write_color(outline.color, 1)
+        } else {
+            write_color(colors[i], samples_per_pixel)
+        };
+        image::Rgb([r, g, b])
+    })
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -53,8 +182,37 @@ fn main() -> Result<(), Box<dyn Error>> {
     let samples_per_pixel = 50;
     let max_depth = 30;
 
-    // let world: HittableList = random_scene();
-    let world = simple_light();
+    let mut registry = MaterialRegistry::with_presets();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--list-materials") {
+        for name in registry.names() {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    let heatmap = args.iter().any(|arg| arg == "--heatmap");
+    let toon = args.iter().any(|arg| arg == "--toon");
+    let accelerator = match args.iter().find_map(|arg| arg.strip_prefix("--accel=")) {
+        Some(name) => Some(name.parse::<Accelerator>()?),
+        None => None,
+    };
+    let subject = args.iter().find_map(|arg| arg.strip_prefix("--material="));
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(String::as_str)
+        .unwrap_or("image.png");
+
+    // let world: HittableList = random_scene(&mut registry)?;
+    let world = match subject {
+        Some(name) => {
+            let material = registry.get(name)?;
+            simple_light_with(&mut registry, material)?
+        }
+        None => simple_light(&mut registry)?,
+    };
 
     let lookfrom = Vec3::new(26.0, 3.0, 6.0);
     let lookat = Vec3::new(0.0, 2.0, 0.0);
@@ -74,22 +232,104 @@ fn main() -> Result<(), Box<dyn Error>> {
         1.0,
     );
 
-    let mut last_y = 0;
-    let image_buffer = image::ImageBuffer::from_fn(image_width, image_height, |x, y| {
-        if y > last_y {
-            last_y = y;
-            println!("Scanline {} done!", last_y);
+    if heatmap {
+        if !stats::ENABLED {
+            return Err(
+                "--heatmap needs the traversal counters: build with `--features stats`".into(),
+            );
         }
 
-        let mut pixel_color = Vec3::default();
-        for _ in 0..samples_per_pixel {
-            let u = (x as f64 + random_double()) / (image_width as f64 - 1.0);
-            let v = 1.0 - (y as f64 + random_double()) / (image_height as f64 - 1.0);
+        let image_buffer = match accelerator {
+            None | Some(Accelerator::Bvh) => {
+                let bvh = BvhNode::new(world, camera.time0(), camera.time1());
+                println!("{}", bvh.stats());
+                render_heatmap(&bvh, &camera, image_width, image_height)
+            }
+            Some(accelerator) => {
+                let world = accelerator.build(world, camera.time0(), camera.time1());
+                render_heatmap(world.as_ref(), &camera, image_width, image_height)
+            }
+        };
+
+        println!("Saving to: {}", path);
+        image_buffer.save(path)?;
+
+        return Ok(());
+    }
+
+    // Scenes go in a BVH unless another accelerator is asked for.
+    let accelerator = accelerator.unwrap_or(Accelerator::Bvh);
+    let world = vec![accelerator.build(world, camera.time0(), camera.time1())];
+
+    let background = Vec3(0.0, 0.0, 0.0);
+
+    if toon {
+        let image_buffer = render_toon(
+            &world,
+            &camera,
+            image_width,
+            image_height,
+            samples_per_pixel,
+            background,
+        );
+
+        println!("Saving to: {}", path);
+        image_buffer.save(path)?;
+
+        return Ok(());
+    }
+
+    // Primary rays are traced a tile at a time as packets, one packet per sample;
+    // the bounces after the first hit are traced ray by ray. Only the BVH culls
+    // subtrees against a packet's frustum; the other accelerators trace its rays
+    // one by one. Only the primary hits
+    // get ray differentials, so only they read textures from the mip pyramid.
+    let (du, dv) = (
+        1.0 / (image_width as f64 - 1.0),
+        1.0 / (image_height as f64 - 1.0),
+    );
+    let mut pixels = vec![Vec3::default(); (image_width * image_height) as usize];
+
+    for tile_y in (0..image_height).step_by(TILE_SIZE as usize) {
+        for tile_x in (0..image_width).step_by(TILE_SIZE as usize) {
+            let tile: Vec<(u32, u32)> = (tile_y..u32::min(tile_y + TILE_SIZE, image_height))
+                .flat_map(|y| {
+                    (tile_x..u32::min(tile_x + TILE_SIZE, image_width)).map(move |x| (x, y))
+                })
+                .collect();
 
-            let ray = camera.get_ray(u, v);
-            pixel_color += ray_color(ray, Vec3(0.0, 0.0, 0.0), &world, max_depth);
+            for _ in 0..samples_per_pixel {
+                let uvs: Vec<(f64, f64)> = tile
+                    .iter()
+                    .map(|&(x, y)| {
+                        let u = (x as f64 + random_double()) / (image_width as f64 - 1.0);
+                        let v = 1.0 - (y as f64 + random_double()) / (image_height as f64 - 1.0);
+                        (u, v)
+                    })
+                    .collect();
+
+                let packet = camera.get_ray_packet(&uvs);
+                let mut records = vec![None; packet.len()];
+                world.hit_packet(&packet, 0.001, f64::MAX, &mut records);
+
+                for ((&(x, y), ray), record) in tile.iter().zip(packet.rays()).zip(records) {
+                    pixels[(y * image_width + x) as usize] += match record {
+                        Some(record) => {
+                            let record =
+                                record.with_differential(&camera.differential(ray, du, dv));
+                            shade(*ray, &record, background, &world, max_depth)
+                        }
+                        None => background,
+                    };
+                }
+            }
         }
-        let (r, g, b) = write_color(pixel_color, samples_per_pixel);
+
+        println!("Tile row {} done!", tile_y / TILE_SIZE + 1);
+    }
+
+    let image_buffer = image::ImageBuffer::from_fn(image_width, image_height, |x, y| {
+        let (r, g, b) = write_color(pixels[(y * image_width + x) as usize], samples_per_pixel);
         image::Rgb([r, g, b])
     });
 
@@ -111,18 +351,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     //     }
     // }
 
-    let args: Vec<String> = std::env::args().collect();
-
-    match args.get(1) {
-        Some(path) => {
-            println!("Saving to: {}", path);
-            image_buffer.save(path)?;
-        }
-        None => {
-            println!("Saving to: image.png");
-            image_buffer.save("image.png")?;
-        }
-    }
+    println!("Saving to: {}", path);
+    image_buffer.save(path)?;
 
     Ok(())
 }
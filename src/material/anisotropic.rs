@@ -0,0 +1,116 @@
+use super::{Material, Reflectance};
+use crate::clamp;
+use crate::hittable::HitRecord;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+
+// A GGX conductor whose roughness differs along the surface's dp/du and dp/dv, so
+// highlights stretch across the grain like brushed metal. Surfaces without a
+// tangent frame get an arbitrary one.
+pub struct AnisotropicMetal {
+    pub reflectance: Reflectance,
+    pub roughness_u: f64,
+    pub roughness_v: f64,
+}
+
+impl AnisotropicMetal {
+    pub fn new(color: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        Self::with_reflectance(Reflectance::Tint(color), roughness_u, roughness_v)
+    }
+
+    pub fn with_reflectance(reflectance: Reflectance, roughness_u: f64, roughness_v: f64) -> Self {
+        AnisotropicMetal {
+            reflectance,
+            roughness_u: clamp(roughness_u, 0.0, 1.0),
+            roughness_v: clamp(roughness_v, 0.0, 1.0),
+        }
+    }
+
+    pub fn brushed_aluminium(roughness_u: f64, roughness_v: f64) -> Self {
+        Self::with_reflectance(Reflectance::ALUMINIUM, roughness_u, roughness_v)
+    }
+}
+
+impl Material for AnisotropicMetal {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::from_normal_tangent(record.normal, record.dpdu);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let ggx = Ggx::anisotropic(self.roughness_u, self.roughness_v);
+        let m = ggx.sample_visible_normal(wo);
+        let wi = microfacet::reflect(wo, m);
+        if wi.z() <= 0.0 {
+            return false;
+        }
+
+        *attenuation = self.reflectance.fresnel(Vec3::dot(wo, m)) * (ggx.g2(wo, wi) / ggx.g1(wo));
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        Some(self.reflectance.fresnel(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    // The mean squared x and y of reflected directions, with dp/du along x.
+    fn spread(material: Rc<dyn Material>) -> (f64, f64) {
+        let ray = Ray::new(Vec3(0.0, 0.0, 1.0), Vec3(0.0, 0.0, -1.0), 0.0);
+        let record = HitRecord::new(
+            Vec3(0.0, 0.0, 0.0),
+            1.0,
+            0.5,
+            0.5,
+            Vec3(0.0, 0.0, 1.0),
+            &material,
+            &ray,
+        )
+        .with_tangents(Vec3(1.0, 0.0, 0.0), Vec3(0.0, 1.0, 0.0));
+
+        let (mut x, mut y, mut count) = (0.0, 0.0, 0);
+        for _ in 0..20_000 {
+            let mut attenuation = Vec3::default();
+            let mut scattered = Ray::default();
+            if material.scatter(&ray, &record, &mut attenuation, &mut scattered) {
+                let direction = Vec3::unit_vector(scattered.direction());
+                x += direction.x() * direction.x();
+                y += direction.y() * direction.y();
+                count += 1;
+            }
+        }
+        (x / count as f64, y / count as f64)
+    }
+
+    #[test]
+    fn lobe_stretches_along_the_rougher_tangent() {
+        let (x, y) = spread(Rc::new(AnisotropicMetal::new(
+            Vec3(1.0, 1.0, 1.0),
+            0.6,
+            0.2,
+        )));
+        assert!(x > 4.0 * y, "spread along dp/du {} and dp/dv {}", x, y);
+
+        let (x, y) = spread(Rc::new(AnisotropicMetal::new(
+            Vec3(1.0, 1.0, 1.0),
+            0.2,
+            0.6,
+        )));
+        assert!(y > 4.0 * x, "spread along dp/du {} and dp/dv {}", x, y);
+    }
+}
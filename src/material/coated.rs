@@ -0,0 +1,194 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::microfacet;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+
+// Bounces between the coat and the base before the path is given up on.
+const MAX_INTERNAL_BOUNCES: usize = 16;
+
+// A smooth dielectric coat, such as varnish, over any other material.
+//
+// Light is reflected or refracted at the coat by its Fresnel term. Refracted light
+// crosses the coat, scatters off the base, and crosses back, where it may be
+// reflected down again. `tint` is the colour transmitted through a thickness of 1
+// at normal incidence; longer paths through the coat absorb more.
+pub struct Coated {
+    pub base: Rc<dyn Material>,
+    pub ior: f64,
+    pub thickness: f64,
+    pub tint: Color,
+}
+
+impl Coated {
+    pub fn new(base: Rc<dyn Material>, ior: f64, thickness: f64, tint: Color) -> Self {
+        Coated {
+            base,
+            ior,
+            thickness,
+            tint,
+        }
+    }
+
+    fn absorption(&self, cos_theta: f64) -> Color {
+        let distance = self.thickness / cos_theta.abs().max(1e-3);
+        Vec3(
+            self.tint.x().powf(distance),
+            self.tint.y().powf(distance),
+            self.tint.z().powf(distance),
+        )
+    }
+}
+
+impl Material for Coated {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let n = record.normal;
+        let wo = -Vec3::unit_vector(ray_in.direction());
+        let cos_o = Vec3::dot(wo, n);
+
+        let mut direction = match microfacet::refract(wo, n, self.ior) {
+            Some(refracted)
+                if random_double() >= microfacet::fresnel_dielectric(cos_o, self.ior) =>
+            {
+                refracted
+            }
+            _ => {
+                *attenuation = Vec3(1.0, 1.0, 1.0);
+                *scattered = Ray::new(record.p, microfacet::reflect(wo, n), ray_in.time());
+                return true;
+            }
+        };
+        let mut throughput = self.absorption(Vec3::dot(direction, n));
+
+        for _ in 0..MAX_INTERNAL_BOUNCES {
+            let mut base_attenuation = Vec3::default();
+            let mut base_scattered = Ray::default();
+            let base_in = Ray::new(record.p, direction, ray_in.time());
+            if !self
+                .base
+                .scatter(&base_in, record, &mut base_attenuation, &mut base_scattered)
+            {
+                return false;
+            }
+            throughput = throughput * base_attenuation;
+
+            // Light the base transmits leaves through its far side, not the coat.
+            let up = Vec3::unit_vector(base_scattered.direction());
+            let cos_up = Vec3::dot(up, n);
+            if cos_up <= 0.0 {
+                *attenuation = throughput;
+                *scattered = base_scattered;
+                return true;
+            }
+            throughput = throughput * self.absorption(cos_up);
+
+            // Seen from inside the coat, the interface faces down and the index
+            // on the far side is that of air.
+            let eta = 1.0 / self.ior;
+            match microfacet::refract(-up, -n, eta) {
+                Some(out) if random_double() >= microfacet::fresnel_dielectric(cos_up, eta) => {
+                    *attenuation = throughput;
+                    *scattered = Ray::new(record.p, out, ray_in.time());
+                    return true;
+                }
+                _ => {
+                    direction = Vec3::reflect(up, n);
+                    throughput = throughput * self.absorption(cos_up);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        self.base.emitted(ray_in, record)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        self.base.opacity(u, v, p)
+    }
+
+    // The base seen through the coat, down and back up at normal incidence.
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        let absorption = self.absorption(1.0);
+        Some(absorption * absorption * self.base.albedo(record)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn hit(material: Rc<dyn Material>, cos_theta: f64) -> (Ray, HitRecord) {
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let direction = Vec3(sin_theta, 0.0, -cos_theta);
+        let ray = Ray::new(-direction, direction, 0.0);
+        let record = HitRecord::new(
+            Vec3(0.0, 0.0, 0.0),
+            1.0,
+            0.5,
+            0.5,
+            Vec3(0.0, 0.0, 1.0),
+            &material,
+            &ray,
+        );
+        (ray, record)
+    }
+
+    // The mean attenuation of every path, and of just the paths that crossed the
+    // coat to the base, which `tint` colours unless it's white.
+    fn mean_attenuation(material: Rc<dyn Material>, cos_theta: f64) -> (Color, Color) {
+        let samples = 100_000;
+        let (ray, record) = hit(material.clone(), cos_theta);
+        let mut all = Vec3(0.0, 0.0, 0.0);
+        let mut through = Vec3(0.0, 0.0, 0.0);
+        let mut crossings = 0;
+        for _ in 0..samples {
+            let mut attenuation = Vec3::default();
+            let mut scattered = Ray::default();
+            if !material.scatter(&ray, &record, &mut attenuation, &mut scattered) {
+                continue;
+            }
+            all += attenuation;
+            if attenuation.z() < 1.0 {
+                through += attenuation;
+                crossings += 1;
+            }
+        }
+        (all / samples as f64, through / crossings.max(1) as f64)
+    }
+
+    #[test]
+    fn clear_coat_over_white_loses_no_energy() {
+        let white = Rc::new(Lambertian::from((1.0, 1.0, 1.0)));
+        let material = Rc::new(Coated::new(white, 1.5, 1.0, Vec3(1.0, 1.0, 1.0)));
+        for &cos_theta in &[1.0, 0.5, 0.1] {
+            let (all, _) = mean_attenuation(material.clone(), cos_theta);
+            assert!((all.x() - 1.0).abs() < 0.01, "cos {}: {:?}", cos_theta, all);
+        }
+    }
+
+    #[test]
+    fn tinted_coat_darkens_more_at_grazing_angles() {
+        let white = Rc::new(Lambertian::from((1.0, 1.0, 1.0)));
+        let material = Rc::new(Coated::new(white, 1.5, 2.0, Vec3(0.9, 0.7, 0.5)));
+        let (_, normal) = mean_attenuation(material.clone(), 1.0);
+        let (_, grazing) = mean_attenuation(material, 0.1);
+
+        // Refraction bends the way in towards the normal, so the extra distance
+        // through the coat at grazing angles is modest but still there.
+        assert!(grazing.z() < normal.z(), "{:?} vs {:?}", grazing, normal);
+        assert!(grazing.y() < normal.y(), "{:?} vs {:?}", grazing, normal);
+    }
+}
@@ -0,0 +1,72 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+
+// Punches holes in any material using the alpha channel of `opacity`, such as a
+// PNG of a leaf. Partial alpha lets that fraction of rays through at random,
+// unless a `threshold` is set, in which case coverage is all or nothing.
+pub struct Cutout {
+    pub material: Rc<dyn Material>,
+    pub opacity: Rc<dyn Texture>,
+    pub threshold: Option<f64>,
+}
+
+impl Cutout {
+    pub fn new(material: Rc<dyn Material>, opacity: Rc<dyn Texture>) -> Self {
+        Cutout {
+            material,
+            opacity,
+            threshold: None,
+        }
+    }
+
+    pub fn with_threshold(self, threshold: f64) -> Self {
+        Cutout {
+            threshold: Some(threshold),
+            ..self
+        }
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.material
+            .scatter(ray_in, record, attenuation, scattered)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        self.material.emitted(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        self.material.eval(ray_in, record, scattered)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        self.material.pdf(ray_in, record, scattered)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        let alpha = self.opacity.alpha(u, v, p) * self.material.opacity(u, v, p);
+
+        match self.threshold {
+            Some(threshold) if alpha >= threshold => 1.0,
+            Some(_) => 0.0,
+            None => alpha,
+        }
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        self.material.albedo(record)
+    }
+}
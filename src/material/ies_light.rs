@@ -0,0 +1,127 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::ies::IesProfile;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, PI};
+
+// An emitting surface whose brightness varies with direction like a measured
+// fixture. Put it on a small shape where the fixture is; the profile's nadir
+// points along `down` and its horizontal angle 0 along `forward`.
+//
+// `scale` is the radiance in the profile's brightest direction, so the profile
+// only shapes the light. Use `with_area` instead to emit the real
+// intensity from a surface of a given area.
+pub struct IesLight {
+    profile: Rc<IesProfile>,
+    frame: Onb,
+    pub color: Color,
+    pub scale: f64,
+}
+
+impl IesLight {
+    pub fn new(profile: Rc<IesProfile>, color: Color) -> Self {
+        IesLight {
+            profile,
+            frame: Onb::from_normal_tangent(Vec3(0.0, -1.0, 0.0), Vec3(1.0, 0.0, 0.0)),
+            color,
+            scale: 1.0,
+        }
+    }
+
+    pub fn with_orientation(self, down: Vec3, forward: Vec3) -> Self {
+        IesLight {
+            frame: Onb::from_normal_tangent(down, forward),
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: f64) -> Self {
+        IesLight { scale, ..self }
+    }
+
+    // Emits the profile's intensity in candela, spread over a light of `area`
+    // facing the viewer.
+    pub fn with_area(self, area: f64) -> Self {
+        let scale = self.profile.max_candela() / area;
+        IesLight { scale, ..self }
+    }
+}
+
+impl Material for IesLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _record: &HitRecord,
+        _attenuation: &mut Vec3,
+        _scattered: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, ray_in: &Ray, _record: &HitRecord) -> Color {
+        let max = self.profile.max_candela();
+        if max <= 0.0 {
+            return Vec3(0.0, 0.0, 0.0);
+        }
+
+        let direction = self.frame.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let vertical = clamp(direction.z(), -1.0, 1.0).acos() * 180.0 / PI;
+        let horizontal = direction.y().atan2(direction.x()) * 180.0 / PI;
+
+        let intensity = self.profile.candela(vertical, horizontal) / max;
+        self.scale * intensity * self.color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A full-circle type C fixture, dimmer at 45 degrees than straight down and
+    // brightest towards horizontal angle 90.
+    const PROFILE: &str = "IESNA:LM-63-2002
+TILT=NONE
+1 1000 1 3 4 1 2 0.5 0.5 0
+1 1 100
+0 45 90
+0 90 180 270
+400 100 0
+400 300 0
+400 200 0
+400 100 0
+";
+
+    // The light seen by a ray leaving it along `leaving`.
+    fn emitted(light: Rc<dyn Material>, leaving: Vec3) -> Color {
+        let ray = Ray::new(Vec3(0.0, 0.0, 0.0), -leaving, 0.0);
+        let record = HitRecord::new(Vec3(0.0, 0.0, 0.0), 1.0, 0.5, 0.5, leaving, &light, &ray);
+        light.emitted(&ray, &record)
+    }
+
+    #[test]
+    fn emission_follows_the_profile() {
+        let profile = Rc::new(IesProfile::parse(PROFILE).unwrap());
+        let light: Rc<dyn Material> =
+            Rc::new(IesLight::new(profile, Vec3(1.0, 0.5, 0.25)).with_scale(2.0));
+        let diagonal = 0.5f64.sqrt();
+
+        // Straight down is the brightest direction, at `scale`.
+        let down = emitted(light.clone(), Vec3(0.0, -1.0, 0.0));
+        assert!((down - Vec3(2.0, 1.0, 0.5)).length() < 1e-9, "{:?}", down);
+
+        // 45 degrees from down, towards `forward` (horizontal 0) and towards
+        // horizontal 90, which is `down` x `forward`.
+        let forward = emitted(light.clone(), Vec3(diagonal, -diagonal, 0.0));
+        assert!((forward.x() - 2.0 * 0.25).abs() < 1e-9, "{:?}", forward);
+        let side = emitted(light.clone(), Vec3(0.0, -diagonal, diagonal));
+        assert!((side.x() - 2.0 * 0.75).abs() < 1e-9, "{:?}", side);
+
+        // Above the fixture is outside the measured range.
+        let up = emitted(light, Vec3(0.0, 1.0, 0.0));
+        assert_eq!(up.x(), 0.0);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, PI};
+
+const THETA_H_RESOLUTION: usize = 90;
+const THETA_D_RESOLUTION: usize = 90;
+const PHI_D_RESOLUTION: usize = 180;
+const TABLE_SIZE: usize = THETA_H_RESOLUTION * THETA_D_RESOLUTION * PHI_D_RESOLUTION;
+
+// The tables store each channel scaled by a different constant.
+const CHANNEL_SCALE: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+#[derive(Debug)]
+pub enum MerlError {
+    Io(io::Error),
+    // The header's table dimensions, if they aren't the standard 90 x 90 x 180.
+    UnexpectedDimensions(i32, i32, i32),
+    Truncated,
+}
+
+impl fmt::Display for MerlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerlError::Io(error) => write!(f, "could not read MERL BRDF: {}", error),
+            MerlError::UnexpectedDimensions(a, b, c) => {
+                write!(f, "unexpected MERL BRDF dimensions {} x {} x {}", a, b, c)
+            }
+            MerlError::Truncated => write!(f, "MERL BRDF file is truncated"),
+        }
+    }
+}
+
+impl Error for MerlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MerlError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MerlError {
+    fn from(error: io::Error) -> Self {
+        MerlError::Io(error)
+    }
+}
+
+// An isotropic BRDF measured by the MERL gonioreflectometer, tabulated over the
+// half and difference angles of Rusinkiewicz's parameterisation. Directions are
+// sampled from the cosine-weighted hemisphere.
+pub struct Merl {
+    // Red, then green, then blue, each `TABLE_SIZE` long and already scaled.
+    table: Vec<f64>,
+}
+
+impl Merl {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MerlError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    // The binary format: three little-endian i32 table dimensions, then the three
+    // channels as little-endian f64s.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MerlError> {
+        if bytes.len() < 12 {
+            return Err(MerlError::Truncated);
+        }
+
+        let dimension = |i: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[4 * i..4 * i + 4]);
+            i32::from_le_bytes(word)
+        };
+        let dimensions = (dimension(0), dimension(1), dimension(2));
+        if dimensions
+            != (
+                THETA_H_RESOLUTION as i32,
+                THETA_D_RESOLUTION as i32,
+                PHI_D_RESOLUTION as i32,
+            )
+        {
+            let (a, b, c) = dimensions;
+            return Err(MerlError::UnexpectedDimensions(a, b, c));
+        }
+
+        let data = &bytes[12..];
+        if data.len() < 3 * TABLE_SIZE * 8 {
+            return Err(MerlError::Truncated);
+        }
+
+        let table = data
+            .chunks_exact(8)
+            .take(3 * TABLE_SIZE)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut word = [0; 8];
+                word.copy_from_slice(chunk);
+                f64::from_le_bytes(word) * CHANNEL_SCALE[i / TABLE_SIZE]
+            })
+            .collect();
+
+        Ok(Merl { table })
+    }
+
+    // The BRDF for local directions `wo` and `wi` on the same side as +z.
+    fn brdf(&self, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Vec3(0.0, 0.0, 0.0);
+        }
+
+        let index = lookup_index(wo, wi);
+        let channel = |c: usize| self.table[c * TABLE_SIZE + index].max(0.0);
+
+        Vec3(channel(0), channel(1), channel(2))
+    }
+
+    fn local_directions(ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = onb.to_local(Vec3::unit_vector(scattered.direction()));
+        (wo, wi)
+    }
+}
+
+// Rotates `v` about the z axis, then the y axis.
+fn rotate_z_then_y(v: Vec3, z_angle: f64, y_angle: f64) -> Vec3 {
+    let (sin_z, cos_z) = z_angle.sin_cos();
+    let v = Vec3(
+        v.x() * cos_z - v.y() * sin_z,
+        v.x() * sin_z + v.y() * cos_z,
+        v.z(),
+    );
+
+    let (sin_y, cos_y) = y_angle.sin_cos();
+    Vec3(
+        v.x() * cos_y + v.z() * sin_y,
+        v.y(),
+        -v.x() * sin_y + v.z() * cos_y,
+    )
+}
+
+// The table index for a pair of directions, via their half vector and the
+// incoming direction relative to it.
+fn lookup_index(wo: Vec3, wi: Vec3) -> usize {
+    let half = Vec3::unit_vector(wo + wi);
+    let theta_h = half.z().min(1.0).acos();
+    let phi_h = half.y().atan2(half.x());
+
+    let difference = rotate_z_then_y(wi, -phi_h, -theta_h);
+    let theta_d = clamp(difference.z(), -1.0, 1.0).acos();
+    let mut phi_d = difference.y().atan2(difference.x());
+
+    // Reciprocity makes phi_d and phi_d + pi the same.
+    if phi_d < 0.0 {
+        phi_d += PI;
+    }
+
+    // Half angles are stored on a square-root scale, to put more samples near the
+    // specular peak.
+    let theta_h_index = ((theta_h / (0.5 * PI)).sqrt() * THETA_H_RESOLUTION as f64) as usize;
+    let theta_d_index = (theta_d / (0.5 * PI) * THETA_D_RESOLUTION as f64) as usize;
+    let phi_d_index = (phi_d / PI * PHI_D_RESOLUTION as f64) as usize;
+
+    let theta_h_index = theta_h_index.min(THETA_H_RESOLUTION - 1);
+    let theta_d_index = theta_d_index.min(THETA_D_RESOLUTION - 1);
+    let phi_d_index = phi_d_index.min(PHI_D_RESOLUTION - 1);
+
+    (theta_h_index * THETA_D_RESOLUTION + theta_d_index) * PHI_D_RESOLUTION + phi_d_index
+}
+
+impl Material for Merl {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = Vec3::rand_cosine_direction();
+
+        // eval / pdf, where the cosines cancel.
+        *attenuation = PI * self.brdf(wo, wi);
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let (wo, wi) = Self::local_directions(ray_in, record, scattered);
+        Some(wi.z().max(0.0) * self.brdf(wo, wi))
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let (_, wi) = Self::local_directions(ray_in, record, scattered);
+        Some(wi.z().max(0.0) / PI)
+    }
+
+    // The BRDF at normal incidence times pi, which is the albedo of a diffuse
+    // surface.
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        let normal = Vec3(0.0, 0.0, 1.0);
+        Some(PI * self.brdf(normal, normal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A table whose every entry is its own index, in all three channels.
+    fn indexed_table() -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12 + 3 * TABLE_SIZE * 8);
+        for &dimension in &[THETA_H_RESOLUTION, THETA_D_RESOLUTION, PHI_D_RESOLUTION] {
+            bytes.extend_from_slice(&(dimension as i32).to_le_bytes());
+        }
+        for _ in 0..3 {
+            for index in 0..TABLE_SIZE {
+                bytes.extend_from_slice(&(index as f64).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn direction(theta: f64, phi: f64) -> Vec3 {
+        Vec3(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        )
+    }
+
+    fn index(theta_h: usize, theta_d: usize, phi_d: usize) -> usize {
+        (theta_h * THETA_D_RESOLUTION + theta_d) * PHI_D_RESOLUTION + phi_d
+    }
+
+    #[test]
+    fn looks_up_the_rusinkiewicz_angles() {
+        let normal = Vec3(0.0, 0.0, 1.0);
+        assert_eq!(lookup_index(normal, normal), 0);
+
+        // Mirror directions have their half vector along the normal, so theta_h
+        // is 0 and theta_d is their angle to the normal. phi_d depends on the
+        // azimuth of a half vector with none, so it isn't checked.
+        let wo = direction(0.6, 0.4);
+        let wi = direction(0.6, 0.4 + PI);
+        let theta_d = (0.6 / (0.5 * PI) * THETA_D_RESOLUTION as f64) as usize;
+        let found = lookup_index(wo, wi);
+        assert_eq!(
+            found / PHI_D_RESOLUTION,
+            index(0, theta_d, 0) / PHI_D_RESOLUTION
+        );
+
+        // Equal directions put the half vector on them, on the square-root
+        // scale, with no difference angle, so again phi_d isn't checked.
+        let w = direction(0.3, 1.0);
+        let theta_h = ((0.3 / (0.5 * PI)).sqrt() * THETA_H_RESOLUTION as f64) as usize;
+        let found = lookup_index(w, w);
+        assert_eq!(
+            found / PHI_D_RESOLUTION,
+            index(theta_h, 0, 0) / PHI_D_RESOLUTION
+        );
+
+        // Reciprocity: swapping the directions finds the same entry.
+        let (a, b) = (direction(0.2, 0.1), direction(1.1, 2.5));
+        assert_eq!(lookup_index(a, b), lookup_index(b, a));
+    }
+
+    #[test]
+    fn scales_each_channel_and_rejects_bad_files() {
+        let bytes = indexed_table();
+        let merl = Merl::from_bytes(&bytes).unwrap();
+
+        let w = direction(0.3, 1.0);
+        let i = lookup_index(w, w) as f64;
+        let value = merl.brdf(w, w);
+        assert!((value.x() - i * CHANNEL_SCALE[0]).abs() < 1e-9);
+        assert!((value.y() - i * CHANNEL_SCALE[1]).abs() < 1e-9);
+        assert!((value.z() - i * CHANNEL_SCALE[2]).abs() < 1e-9);
+
+        assert!(matches!(
+            Merl::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MerlError::Truncated)
+        ));
+        let mut wrong = bytes[..12].to_vec();
+        wrong[..4].copy_from_slice(&45i32.to_le_bytes());
+        assert!(matches!(
+            Merl::from_bytes(&wrong),
+            Err(MerlError::UnexpectedDimensions(45, 90, 180))
+        ));
+    }
+}
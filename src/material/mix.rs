@@ -0,0 +1,78 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::clamp;
+use crate::hittable::HitRecord;
+use crate::random_double;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+
+// Blends two materials by the luminance of `mask`: 0 is all `a`, 1 is all `b`.
+// Each scatter picks one of them at random with those odds.
+pub struct MixMaterial {
+    pub a: Rc<dyn Material>,
+    pub b: Rc<dyn Material>,
+    pub mask: Rc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Rc<dyn Material>, b: Rc<dyn Material>, mask: Rc<dyn Texture>) -> Self {
+        MixMaterial { a, b, mask }
+    }
+
+    fn weight(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        clamp(self.mask.value(u, v, p).luminance(), 0.0, 1.0)
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let material = if random_double() < self.weight(record.u, record.v, record.p) {
+            &self.b
+        } else {
+            &self.a
+        };
+
+        material.scatter(ray_in, record, attenuation, scattered)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        let t = self.weight(record.u, record.v, record.p);
+        (1.0 - t) * self.a.emitted(ray_in, record) + t * self.b.emitted(ray_in, record)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let t = self.weight(record.u, record.v, record.p);
+        let a = self.a.eval(ray_in, record, scattered)?;
+        let b = self.b.eval(ray_in, record, scattered)?;
+        Some((1.0 - t) * a + t * b)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let t = self.weight(record.u, record.v, record.p);
+        let a = self.a.pdf(ray_in, record, scattered)?;
+        let b = self.b.pdf(ray_in, record, scattered)?;
+        Some((1.0 - t) * a + t * b)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        let t = self.weight(u, v, p);
+        (1.0 - t) * self.a.opacity(u, v, p) + t * self.b.opacity(u, v, p)
+    }
+
+    // A material that doesn't scatter counts as black, unless neither does.
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        let t = self.weight(record.u, record.v, record.p);
+        match (self.a.albedo(record), self.b.albedo(record)) {
+            (None, None) => None,
+            (a, b) => Some((1.0 - t) * a.unwrap_or_default() + t * b.unwrap_or_default()),
+        }
+    }
+}
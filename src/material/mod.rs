@@ -0,0 +1,726 @@
+use std::{convert::TryFrom, rc::Rc};
+
+use crate::hittable::HitRecord;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{refract, Vec3, Color};
+use crate::{
+    clamp,
+    texture::{CheckerTexture, ImageTexture, SolidColor, Texture, TextureError},
+    PI,
+};
+
+mod anisotropic;
+mod coated;
+mod cutout;
+mod ies_light;
+mod merl;
+mod mix;
+mod mtl;
+mod normal_map;
+mod oren_nayar;
+mod principled;
+mod registry;
+mod rough_dielectric;
+mod sheen;
+mod subsurface;
+mod thin_film;
+mod toon;
+
+pub use anisotropic::AnisotropicMetal;
+pub use coated::Coated;
+pub use cutout::Cutout;
+pub use ies_light::IesLight;
+pub use merl::{Merl, MerlError};
+pub use mix::MixMaterial;
+pub use mtl::{MtlError, MtlLibrary};
+pub use normal_map::{BumpMapped, NormalMapped};
+pub use oren_nayar::OrenNayar;
+pub use principled::Principled;
+pub use registry::{MaterialRegistry, RegistryError};
+pub use rough_dielectric::RoughDielectric;
+pub use sheen::Sheen;
+pub use subsurface::Subsurface;
+pub use thin_film::{Substrate, ThinFilm};
+pub use toon::Toon;
+
+fn schlick(cosine: f64, ref_ind: f64) -> f64 {
+    let r0 = (1.0 - ref_ind) / (1.0 + ref_ind);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
+}
+
+pub trait Material {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool;
+
+    fn emitted(&self, _ray_in: &Ray, _record: &HitRecord) -> Color {
+        Vec3(0.0, 0.0, 0.0)
+    }
+
+    // The BRDF times the cosine of `scattered` with the normal: the attenuation
+    // per unit solid angle of light bouncing from `scattered` back along `ray_in`.
+    // `None` if the material can't be evaluated in arbitrary directions, either
+    // because it only reflects or refracts in single directions or because it
+    // only knows how to sample itself; callers then have to use `scatter`.
+    fn eval(&self, _ray_in: &Ray, _record: &HitRecord, _scattered: &Ray) -> Option<Color> {
+        None
+    }
+
+    // The probability density per unit solid angle of `scatter` choosing
+    // `scattered`, with `None` exactly when `eval` is.
+    fn pdf(&self, _ray_in: &Ray, _record: &HitRecord, _scattered: &Ray) -> Option<f64> {
+        None
+    }
+
+    // The colour of the surface at a glance, for previews like the toon
+    // integrator that want it without sampling `scatter`. `None` for materials
+    // that don't scatter light, such as lights.
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        None
+    }
+
+    // The chance that a ray hitting the surface here stops rather than passing
+    // straight through it.
+    fn opacity(&self, _u: f64, _v: f64, _p: Vec3) -> f64 {
+        1.0
+    }
+}
+
+pub struct Lambertian {
+    pub albedo: Rc<dyn Texture>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Rc<dyn Texture>) -> Self {
+        Lambertian { albedo }
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let scatter_direction = record.normal + Vec3::rand_unit_vector();
+        *scattered = Ray::new(record.p, scatter_direction, ray_in.time());
+        *attenuation = self.albedo.value_at(record);
+        true
+    }
+
+    // Scattering is cosine weighted, so albedo / pi times the cosine is exactly the
+    // pdf times the albedo.
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let pdf = self.pdf(ray_in, record, scattered)?;
+        Some(pdf * self.albedo.value_at(record))
+    }
+
+    fn pdf(&self, _ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let cosine = Vec3::dot(record.normal, Vec3::unit_vector(scattered.direction()));
+        Some(cosine.max(0.0) / PI)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        Some(self.albedo.value_at(record))
+    }
+}
+
+impl From<(f64, f64, f64)> for Lambertian {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        let color = Vec3(x, y, z);
+        let texture = SolidColor::new(color);
+        Self {
+            albedo: Rc::new(texture),
+        }
+    }
+}
+
+impl From<Vec3> for Lambertian {
+    fn from(color: Vec3) -> Self {
+        Self::from((color.x(), color.y(), color.z()))
+    }
+}
+
+impl TryFrom<&str> for Lambertian {
+    type Error = TextureError;
+
+    fn try_from(path: &str) -> Result<Self, Self::Error> {
+        let texture = ImageTexture::new(path)?;
+        Ok(Self {
+            albedo: Rc::new(texture),
+        })
+    }
+}
+
+impl From<(Vec3, Vec3)> for Lambertian {
+    fn from((color1, color2): (Vec3, Vec3)) -> Self {
+        let texture = CheckerTexture::new(color1, color2);
+        Self {
+            albedo: Rc::new(texture),
+        }
+    }
+}
+
+// How much light a metal reflects at each angle: either an artist-friendly tint
+// used as the Schlick reflectance at normal incidence, or a measured complex
+// index of refraction.
+#[derive(Debug, Copy, Clone)]
+pub enum Reflectance {
+    Tint(Color),
+    Conductor { eta: Color, k: Color },
+}
+
+impl Reflectance {
+    // RGB fits of measured (n, k) spectra.
+    pub const GOLD: Reflectance = Reflectance::Conductor {
+        eta: Vec3(0.143, 0.374, 1.442),
+        k: Vec3(3.983, 2.385, 1.603),
+    };
+
+    pub const COPPER: Reflectance = Reflectance::Conductor {
+        eta: Vec3(0.200, 0.924, 1.102),
+        k: Vec3(3.912, 2.452, 2.142),
+    };
+
+    pub const ALUMINIUM: Reflectance = Reflectance::Conductor {
+        eta: Vec3(1.657, 0.880, 0.521),
+        k: Vec3(9.224, 6.270, 4.837),
+    };
+
+    fn fresnel(&self, cos_theta: f64) -> Color {
+        match *self {
+            Reflectance::Tint(f0) => microfacet::fresnel_schlick(cos_theta, f0),
+            Reflectance::Conductor { eta, k } => microfacet::fresnel_conductor(cos_theta, eta, k),
+        }
+    }
+}
+
+// A GGX microfacet conductor. A roughness of 0 is a perfect mirror. Roughness
+// isn't on the same scale as the fuzz of the old fuzzed-mirror metal, so the
+// tinted constructor is `rough` rather than `new`.
+pub struct Metal {
+    pub reflectance: Reflectance,
+    pub roughness: f64,
+}
+
+impl Metal {
+    pub fn rough(color: Vec3, roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::Tint(color), roughness)
+    }
+
+    pub fn conductor(eta: Color, k: Color, roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::Conductor { eta, k }, roughness)
+    }
+
+    pub fn gold(roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::GOLD, roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::COPPER, roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::with_reflectance(Reflectance::ALUMINIUM, roughness)
+    }
+
+    fn with_reflectance(reflectance: Reflectance, roughness: f64) -> Self {
+        Metal {
+            reflectance,
+            roughness: clamp(roughness, 0.0, 1.0),
+        }
+    }
+
+    // The local incoming and scattered directions and their half vector, or
+    // `None` if either direction is below the surface.
+    fn local_directions(
+        ray_in: &Ray,
+        record: &HitRecord,
+        scattered: &Ray,
+    ) -> Option<(Vec3, Vec3, Vec3)> {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = onb.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return None;
+        }
+        Some((wo, wi, Vec3::unit_vector(wo + wi)))
+    }
+}
+
+impl Material for Metal {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let m = ggx.sample_visible_normal(wo);
+        let wi = microfacet::reflect(wo, m);
+        if wi.z() <= 0.0 {
+            return false;
+        }
+
+        // With visible normal sampling the D and cosine terms cancel against the
+        // pdf, leaving the Fresnel term and the shadowing not already accounted for.
+        *attenuation = self.reflectance.fresnel(Vec3::dot(wo, m)) * (ggx.g2(wo, wi) / ggx.g1(wo));
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    // F D G2 / (4 cos(wo)), with the cosine of `wi` cancelled. A mirror only
+    // reflects in one direction, so it can't be evaluated.
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        if self.roughness == 0.0 {
+            return None;
+        }
+
+        let (wo, wi, m) = match Self::local_directions(ray_in, record, scattered) {
+            Some(directions) => directions,
+            None => return Some(Vec3(0.0, 0.0, 0.0)),
+        };
+        let ggx = Ggx::new(self.roughness);
+        let weight = ggx.d(m) * ggx.g2(wo, wi) / (4.0 * wo.z());
+        Some(self.reflectance.fresnel(Vec3::dot(wo, m)) * weight)
+    }
+
+    // The visible normal density G1 D (wo . m) / cos(wo), times the Jacobian
+    // 1 / (4 (wo . m)) of reflecting about m.
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        if self.roughness == 0.0 {
+            return None;
+        }
+
+        let (wo, _, m) = match Self::local_directions(ray_in, record, scattered) {
+            Some(directions) => directions,
+            None => return Some(0.0),
+        };
+        let ggx = Ggx::new(self.roughness);
+        Some(ggx.g1(wo) * ggx.d(m) / (4.0 * wo.z()))
+    }
+
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        Some(self.reflectance.fresnel(1.0))
+    }
+}
+
+pub struct Dielectric {
+    ref_idx: f64,
+}
+
+impl Dielectric {
+    pub fn new(ref_idx: f64) -> Self {
+        Dielectric { ref_idx }
+    }
+}
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        *attenuation = Vec3(1.0, 1.0, 1.0);
+        let etai_over_etat = if record.front_face {
+            1.0 / self.ref_idx
+        } else {
+            self.ref_idx
+        };
+
+        let unit_direction = Vec3::unit_vector(ray_in.direction());
+
+        let cos_theta = f64::min(Vec3::dot(-unit_direction, record.normal), 1.0);
+        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+        if etai_over_etat * sin_theta > 1.0 {
+            let reflected = Vec3::reflect(unit_direction, record.normal);
+            *scattered = Ray::new(record.p, reflected, ray_in.time());
+            return true;
+        }
+
+        let reflect_prob = schlick(cos_theta, etai_over_etat);
+        if crate::random_double() < reflect_prob {
+            let reflected = Vec3::reflect(unit_direction, record.normal);
+            *scattered = Ray::new(record.p, reflected, ray_in.time());
+            return true;
+        }
+
+        let refracted = refract(unit_direction, record.normal, etai_over_etat);
+        *scattered = Ray::new(record.p, refracted, ray_in.time());
+        true
+    }
+
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        Some(Vec3(1.0, 1.0, 1.0))
+    }
+}
+
+// How bright a light is: either a plain multiplier on its texture, or the total
+// power it gives off, spread over `area`. For power, the texture should be a tint
+// with a luminance of 1.
+#[derive(Debug, Copy, Clone)]
+pub enum Intensity {
+    Scale(f64),
+    Watts { watts: f64, area: f64 },
+}
+
+// Luminous efficacy at 555 nm, the peak of human sensitivity.
+const LUMENS_PER_WATT: f64 = 683.0;
+
+pub struct DiffuseLight {
+    emit: Rc<dyn Texture>,
+    pub intensity: Intensity,
+    pub one_sided: bool,
+    // Radiance falls off as cos^n away from the normal; 0 is a diffuse emitter.
+    pub spot_exponent: f64,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Rc<dyn Texture>) -> Self {
+        DiffuseLight {
+            emit,
+            intensity: Intensity::Scale(1.0),
+            one_sided: false,
+            spot_exponent: 0.0,
+        }
+    }
+
+    // Only the front face, the side the outward normal points to, glows.
+    pub fn one_sided(self) -> Self {
+        DiffuseLight {
+            one_sided: true,
+            ..self
+        }
+    }
+
+    // Focuses the light around the normal. The total power stays the same.
+    pub fn spot(self, exponent: f64) -> Self {
+        DiffuseLight {
+            spot_exponent: exponent.max(0.0),
+            ..self
+        }
+    }
+
+    pub fn with_scale(self, scale: f64) -> Self {
+        DiffuseLight {
+            intensity: Intensity::Scale(scale),
+            ..self
+        }
+    }
+
+    pub fn with_watts(self, watts: f64, area: f64) -> Self {
+        DiffuseLight {
+            intensity: Intensity::Watts { watts, area },
+            ..self
+        }
+    }
+
+    pub fn with_lumens(self, lumens: f64, area: f64) -> Self {
+        self.with_watts(lumens / LUMENS_PER_WATT, area)
+    }
+
+    // The radiance straight along the normal per unit of texture colour.
+    fn scale(&self) -> f64 {
+        match self.intensity {
+            Intensity::Scale(scale) => scale,
+            Intensity::Watts { watts, area } => {
+                // A diffuse emitter of radiance L gives off pi * L per unit area
+                // from each glowing side.
+                let sides = if self.one_sided { 1.0 } else { 2.0 };
+                watts / (PI * area * sides)
+            }
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _record: &HitRecord,
+        _attenuation: &mut Vec3,
+        _scattered: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        if self.one_sided && !record.front_face {
+            return Vec3(0.0, 0.0, 0.0);
+        }
+
+        let mut scale = self.scale();
+        if self.spot_exponent > 0.0 {
+            // The normal always faces the viewer, so this is the cosine of the
+            // angle the light leaves at. (n + 2) / 2 keeps the power unchanged.
+            let cos_theta = Vec3::dot(record.normal, -Vec3::unit_vector(ray_in.direction()));
+            let n = self.spot_exponent;
+            scale *= 0.5 * (n + 2.0) * cos_theta.max(0.0).powf(n);
+        }
+
+        scale * self.emit.value_at(record)
+    }
+}
+
+impl From<(f64, f64, f64)> for DiffuseLight {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        let color = Vec3(x, y, z);
+        let texture = SolidColor::new(color);
+        Self::new(Rc::new(texture))
+    }
+}
+
+impl From<Vec3> for DiffuseLight {
+    fn from(color: Vec3) -> Self {
+        Self::from((color.x(), color.y(), color.z()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(material: Rc<dyn Material>, incoming: Vec3) -> (Ray, HitRecord) {
+        let ray = Ray::new(-incoming, incoming, 0.0);
+        let record = HitRecord::new(
+            Vec3(0.0, 0.0, 0.0),
+            1.0,
+            0.5,
+            0.5,
+            Vec3(0.0, 0.0, 1.0),
+            &material,
+            &ray,
+        );
+        (ray, record)
+    }
+
+    // Integrates `eval` over the sphere by uniform sampling, which is what the
+    // mean attenuation from `scatter` estimates, along with the pdf, which
+    // should integrate to 1.
+    fn integrate(material: &dyn Material, ray: &Ray, record: &HitRecord) -> (Color, f64) {
+        let samples = 200_000;
+        let mut eval = Vec3(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+        for _ in 0..samples {
+            let scattered = Ray::new(record.p, Vec3::rand_unit_vector(), 0.0);
+            eval += material.eval(ray, record, &scattered).unwrap();
+            pdf += material.pdf(ray, record, &scattered).unwrap();
+        }
+
+        let scale = 4.0 * PI / samples as f64;
+        (scale * eval, scale * pdf)
+    }
+
+    fn mean_attenuation(material: &dyn Material, ray: &Ray, record: &HitRecord) -> Color {
+        let samples = 200_000;
+        let mut sum = Vec3(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let mut attenuation = Vec3::default();
+            let mut scattered = Ray::default();
+            if material.scatter(ray, record, &mut attenuation, &mut scattered) {
+                sum += attenuation;
+            }
+        }
+        sum / samples as f64
+    }
+
+    fn assert_close(actual: Color, expected: Color, tolerance: f64) {
+        let difference = actual - expected;
+        assert!(
+            difference.x().abs() < tolerance
+                && difference.y().abs() < tolerance
+                && difference.z().abs() < tolerance,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn lambertian_eval_over_pdf_is_the_scatter_attenuation() {
+        let material: Rc<dyn Material> = Rc::new(Lambertian::from((0.2, 0.5, 0.8)));
+        let (ray, record) = hit(material.clone(), Vec3::unit_vector(Vec3(1.0, 0.0, -2.0)));
+
+        for _ in 0..1000 {
+            let mut attenuation = Vec3::default();
+            let mut scattered = Ray::default();
+            assert!(material.scatter(&ray, &record, &mut attenuation, &mut scattered));
+
+            let eval = material.eval(&ray, &record, &scattered).unwrap();
+            let pdf = material.pdf(&ray, &record, &scattered).unwrap();
+            if pdf > 1e-6 {
+                assert_close(eval / pdf, attenuation, 1e-9);
+            }
+        }
+
+        let (eval, pdf) = integrate(material.as_ref(), &ray, &record);
+        assert!((pdf - 1.0).abs() < 0.02, "pdf integrates to {}", pdf);
+        assert_close(eval, Vec3(0.2, 0.5, 0.8), 0.02);
+    }
+
+    #[test]
+    fn eval_integrates_to_the_mean_scatter_attenuation() {
+        let lambertian: Rc<dyn Material> = Rc::new(Lambertian::from((0.9, 0.6, 0.3)));
+        let materials: Vec<Rc<dyn Material>> = vec![
+            Rc::new(OrenNayar::new(
+                Rc::new(SolidColor::new(Vec3(0.9, 0.6, 0.3))),
+                30.0,
+            )),
+            Rc::new(Sheen::new(
+                lambertian.clone(),
+                Rc::new(SolidColor::new(Vec3(0.6, 0.6, 0.6))),
+                0.5,
+            )),
+            Rc::new(MixMaterial::new(
+                lambertian.clone(),
+                Rc::new(OrenNayar::new(
+                    Rc::new(SolidColor::new(Vec3(0.1, 0.2, 0.3))),
+                    20.0,
+                )),
+                Rc::new(SolidColor::new(Vec3(0.25, 0.25, 0.25))),
+            )),
+            Rc::new(Cutout::new(
+                lambertian,
+                Rc::new(SolidColor::new(Vec3(1.0, 1.0, 1.0))),
+            )),
+        ];
+
+        for material in materials {
+            let (ray, record) = hit(material.clone(), Vec3::unit_vector(Vec3(1.0, 1.0, -1.5)));
+            let (eval, pdf) = integrate(material.as_ref(), &ray, &record);
+            assert!((pdf - 1.0).abs() < 0.02, "pdf integrates to {}", pdf);
+            assert_close(eval, mean_attenuation(material.as_ref(), &ray, &record), 0.02);
+        }
+    }
+
+    #[test]
+    fn rough_metal_eval_over_pdf_is_the_scatter_attenuation() {
+        let material: Rc<dyn Material> = Rc::new(Metal::gold(0.6));
+        let (ray, record) = hit(material.clone(), Vec3::unit_vector(Vec3(1.0, 0.5, -1.5)));
+
+        let samples = 20_000;
+        let mut scattered_count = 0;
+        for _ in 0..samples {
+            let mut attenuation = Vec3::default();
+            let mut scattered = Ray::default();
+            if !material.scatter(&ray, &record, &mut attenuation, &mut scattered) {
+                continue;
+            }
+            scattered_count += 1;
+
+            let eval = material.eval(&ray, &record, &scattered).unwrap();
+            let pdf = material.pdf(&ray, &record, &scattered).unwrap();
+            assert_close(eval / pdf, attenuation, 1e-9);
+        }
+
+        // Reflections the visible normals send below the surface are lost, so the
+        // pdf integrates to the share of samples that do scatter.
+        let (eval, pdf) = integrate(material.as_ref(), &ray, &record);
+        let kept = scattered_count as f64 / samples as f64;
+        assert!((pdf - kept).abs() < 0.02, "pdf integrates to {}, not {}", pdf, kept);
+        assert_close(eval, mean_attenuation(material.as_ref(), &ray, &record), 0.02);
+    }
+
+    #[test]
+    fn specular_materials_cannot_be_evaluated() {
+        let materials: Vec<Rc<dyn Material>> = vec![
+            Rc::new(Dielectric::new(1.5)),
+            Rc::new(Metal::rough(Vec3(0.9, 0.9, 0.9), 0.0)),
+        ];
+
+        for material in materials {
+            let (ray, record) = hit(material.clone(), Vec3(0.0, 0.0, -1.0));
+            let scattered = Ray::new(record.p, Vec3(0.0, 0.0, 1.0), 0.0);
+            assert!(material.eval(&ray, &record, &scattered).is_none());
+            assert!(material.pdf(&ray, &record, &scattered).is_none());
+        }
+    }
+
+    // The power a flat light of `area` gives off from both sides, integrating its
+    // radiance times the cosine over every direction it leaves in. Emission
+    // doesn't depend on the azimuth, so the integral runs over the polar angle.
+    fn radiated_power(light: Rc<dyn Material>, area: f64) -> f64 {
+        let steps = 20_000;
+        let step = PI / steps as f64;
+        let mut power = 0.0;
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) * step;
+            let leaving = Vec3(theta.sin(), 0.0, theta.cos());
+            let (ray, record) = hit(light.clone(), -leaving);
+            let radiance = light.emitted(&ray, &record).x();
+            power += radiance * theta.cos().abs() * 2.0 * PI * theta.sin() * step;
+        }
+        area * power
+    }
+
+    #[test]
+    fn one_sided_light_is_black_from_behind() {
+        let light: Rc<dyn Material> = Rc::new(DiffuseLight::from((4.0, 4.0, 4.0)).one_sided());
+
+        let (ray, record) = hit(light.clone(), Vec3(0.0, 0.0, -1.0));
+        assert!(record.front_face);
+        assert_close(light.emitted(&ray, &record), Vec3(4.0, 4.0, 4.0), 1e-12);
+
+        let (ray, record) = hit(light.clone(), Vec3(0.0, 0.0, 1.0));
+        assert!(!record.front_face);
+        assert_close(light.emitted(&ray, &record), Vec3(0.0, 0.0, 0.0), 1e-12);
+    }
+
+    #[test]
+    fn lights_give_off_the_power_they_are_given() {
+        for &one_sided in &[false, true] {
+            for &exponent in &[0.0, 1.0, 8.0, 64.0] {
+                let mut light = DiffuseLight::from((1.0, 1.0, 1.0))
+                    .with_watts(25.0, 2.0)
+                    .spot(exponent);
+                if one_sided {
+                    light = light.one_sided();
+                }
+
+                // The spot factor redistributes power rather than adding any.
+                let power = radiated_power(Rc::new(light), 2.0);
+                assert!(
+                    (power - 25.0).abs() < 1e-3,
+                    "one sided {}, exponent {}: {} W",
+                    one_sided,
+                    exponent,
+                    power
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lumens_are_watts_at_683_lumens_per_watt() {
+        let lumens: Rc<dyn Material> =
+            Rc::new(DiffuseLight::from((1.0, 1.0, 1.0)).with_lumens(1600.0, 0.5));
+        let watts: Rc<dyn Material> =
+            Rc::new(DiffuseLight::from((1.0, 1.0, 1.0)).with_watts(1600.0 / 683.0, 0.5));
+
+        let (ray, record) = hit(lumens.clone(), Vec3::unit_vector(Vec3(0.3, 0.0, -1.0)));
+        assert_close(
+            lumens.emitted(&ray, &record),
+            watts.emitted(&ray, &record),
+            1e-12,
+        );
+        assert!((radiated_power(lumens, 0.5) - 1600.0 / 683.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,426 @@
+use std::{error::Error, fmt, fs, io, path::Path, rc::Rc};
+
+use super::{Cutout, Material, MaterialRegistry, Principled, RegistryError};
+use crate::texture::{ColorSpace, ImageTexture, SearchPath, SolidColor, Texture, TextureError};
+use crate::vec3::{Color, Vec3};
+
+// Illumination models that describe refraction; for anything else a dissolve
+// below 1 means a cutout, not glass, and the material is wrapped in a `Cutout`.
+const REFRACTIVE_ILLUM: [u32; 4] = [4, 6, 7, 9];
+
+#[derive(Debug)]
+pub enum MtlError {
+    Io(io::Error),
+    // A statement whose numbers didn't parse, with its 1-based line number.
+    Syntax {
+        line: usize,
+        statement: String,
+    },
+    Texture {
+        material: String,
+        error: TextureError,
+    },
+}
+
+impl fmt::Display for MtlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MtlError::Io(error) => write!(f, "could not read material library: {}", error),
+            MtlError::Syntax { line, statement } => {
+                write!(f, "malformed statement on line {}: {:?}", line, statement)
+            }
+            MtlError::Texture { material, error } => {
+                write!(f, "material {:?}: {}", material, error)
+            }
+        }
+    }
+}
+
+impl Error for MtlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MtlError::Io(error) => Some(error),
+            MtlError::Syntax { .. } => None,
+            MtlError::Texture { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for MtlError {
+    fn from(error: io::Error) -> Self {
+        MtlError::Io(error)
+    }
+}
+
+// A parameter as written in the file: a constant, a texture map, or missing.
+#[derive(Debug, Default, Clone)]
+struct Parameter {
+    value: Option<Color>,
+    map: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct MtlMaterial {
+    name: String,
+    diffuse: Parameter,
+    specular: Parameter,
+    emission: Parameter,
+    roughness: Parameter,
+    metallic: Parameter,
+    sheen: Parameter,
+    clearcoat: Parameter,
+    shininess: Option<f64>,
+    ior: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+}
+
+// The constant coverage of a non-refractive material with a dissolve below 1.
+struct Dissolve(f64);
+
+impl Texture for Dissolve {
+    fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Color {
+        Vec3(1.0, 1.0, 1.0)
+    }
+
+    fn alpha(&self, _u: f64, _v: f64, _p: Vec3) -> f64 {
+        self.0
+    }
+}
+
+struct Entry {
+    name: String,
+    principled: Rc<Principled>,
+    // `principled`, or a `Cutout` of it.
+    material: Rc<dyn Material>,
+}
+
+// A Wavefront MTL material library read into `Principled` materials, which is
+// what imported meshes use. The classic statements (Kd, Ks, Ke, Ns, Ni, d, Tr,
+// illum) and the PBR extension (Pr, Pm, Ps, Pc) are understood, along with their
+// `map_` textures; anything else is skipped, as MTL readers conventionally do.
+//
+// glTF's metallic-roughness materials map onto `Principled` the same way, but
+// reading them needs a glTF loader, which this crate doesn't have yet.
+pub struct MtlLibrary {
+    materials: Vec<Entry>,
+}
+
+impl MtlLibrary {
+    // Texture maps are looked up next to the library first.
+    pub fn open<P: AsRef<Path>>(path: P, search_path: &SearchPath) -> Result<Self, MtlError> {
+        let source = fs::read_to_string(&path)?;
+        Self::parse(&source, &search_path.clone().for_scene(path))
+    }
+
+    pub fn parse(source: &str, search_path: &SearchPath) -> Result<Self, MtlError> {
+        let materials = parse_materials(source)?
+            .into_iter()
+            .map(|mtl| {
+                let principled = Rc::new(mtl.to_principled(search_path)?);
+                let material: Rc<dyn Material> = match mtl.cutout_opacity() {
+                    Some(opacity) => {
+                        Rc::new(Cutout::new(principled.clone(), Rc::new(Dissolve(opacity))))
+                    }
+                    None => principled.clone(),
+                };
+                Ok(Entry {
+                    name: mtl.name,
+                    principled,
+                    material,
+                })
+            })
+            .collect::<Result<_, MtlError>>()?;
+
+        Ok(MtlLibrary { materials })
+    }
+
+    // Material names in the order the library defines them.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.materials.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Principled> {
+        self.entry(name).map(|entry| &*entry.principled)
+    }
+
+    // The material to render with: `get`'s, cut out if its dissolve asks for it.
+    pub fn material(&self, name: &str) -> Option<Rc<dyn Material>> {
+        self.entry(name).map(|entry| entry.material.clone())
+    }
+
+    // Adds every material to `registry` under its MTL name.
+    pub fn register(self, registry: &mut MaterialRegistry) -> Result<(), RegistryError> {
+        for entry in self.materials {
+            registry.register(&entry.name, entry.material)?;
+        }
+        Ok(())
+    }
+
+    fn entry(&self, name: &str) -> Option<&Entry> {
+        self.materials.iter().find(|entry| entry.name == name)
+    }
+}
+
+fn parse_materials(source: &str) -> Result<Vec<MtlMaterial>, MtlError> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = words.collect();
+
+        let syntax_error = || MtlError::Syntax {
+            line: index + 1,
+            statement: line.to_string(),
+        };
+
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial {
+                name: arguments.join(" "),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        // Statements before the first `newmtl` have nothing to apply to.
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+
+        let numbers = || -> Result<Vec<f64>, MtlError> {
+            let numbers = arguments
+                .iter()
+                .map(|word| word.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| syntax_error())?;
+            if numbers.is_empty() {
+                return Err(syntax_error());
+            }
+            Ok(numbers)
+        };
+        // A single number stands for a grey.
+        let color = || -> Result<Color, MtlError> {
+            match numbers()?.as_slice() {
+                [grey] => Ok(Vec3(*grey, *grey, *grey)),
+                [r, g, b, ..] => Ok(Vec3(*r, *g, *b)),
+                _ => Err(syntax_error()),
+            }
+        };
+        let scalar = || numbers().map(|numbers| numbers[0]);
+        // Options such as `-bm 1.0` come before the file name, so take the last word.
+        let map = || arguments.last().map(|file| file.to_string());
+
+        match keyword {
+            "Kd" => material.diffuse.value = Some(color()?),
+            "Ks" => material.specular.value = Some(color()?),
+            "Ke" => material.emission.value = Some(color()?),
+            "Pr" => material.roughness.value = Some(color()?),
+            "Pm" => material.metallic.value = Some(color()?),
+            "Ps" => material.sheen.value = Some(color()?),
+            "Pc" => material.clearcoat.value = Some(color()?),
+            "map_Kd" => material.diffuse.map = map(),
+            "map_Ks" => material.specular.map = map(),
+            "map_Ke" => material.emission.map = map(),
+            "map_Pr" => material.roughness.map = map(),
+            "map_Pm" => material.metallic.map = map(),
+            "map_Ps" => material.sheen.map = map(),
+            "map_Pc" => material.clearcoat.map = map(),
+            "Ns" => material.shininess = Some(scalar()?),
+            "Ni" => material.ior = Some(scalar()?),
+            "d" => material.dissolve = Some(scalar()?),
+            "Tr" => material.dissolve = Some(1.0 - scalar()?),
+            "illum" => material.illum = Some(scalar()? as u32),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+impl MtlMaterial {
+    fn refractive(&self) -> bool {
+        matches!(self.illum, Some(illum) if REFRACTIVE_ILLUM.contains(&illum))
+    }
+
+    // Coverage for a dissolve that isn't transmission.
+    fn cutout_opacity(&self) -> Option<f64> {
+        match self.dissolve {
+            Some(dissolve) if !self.refractive() && dissolve < 1.0 => Some(dissolve.max(0.0)),
+            _ => None,
+        }
+    }
+
+    fn texture(
+        &self,
+        parameter: &Parameter,
+        color_space: ColorSpace,
+        search_path: &SearchPath,
+    ) -> Result<Option<Rc<dyn Texture>>, MtlError> {
+        let texture: Rc<dyn Texture> = match (&parameter.map, parameter.value) {
+            (Some(map), _) => {
+                let texture =
+                    ImageTexture::find(map, search_path).map_err(|error| MtlError::Texture {
+                        material: self.name.clone(),
+                        error,
+                    })?;
+                Rc::new(texture.with_color_space(color_space))
+            }
+            (None, Some(value)) => Rc::new(SolidColor::new(value)),
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(texture))
+    }
+
+    fn to_principled(&self, search_path: &SearchPath) -> Result<Principled, MtlError> {
+        // Colour maps are sRGB images; maps of scalar parameters hold plain data.
+        let texture = |parameter: &Parameter, color_space: ColorSpace| {
+            self.texture(parameter, color_space, search_path)
+        };
+        let constant = |value: f64| Rc::new(SolidColor::from(value)) as Rc<dyn Texture>;
+
+        let base_color = texture(&self.diffuse, ColorSpace::Srgb)?
+            .unwrap_or_else(|| Rc::new(SolidColor::new(Vec3(0.8, 0.8, 0.8))));
+        let mut principled = Principled::new(base_color);
+
+        if let Some(specular) = texture(&self.specular, ColorSpace::Srgb)? {
+            principled = principled.with_specular(specular);
+        }
+        if let Some(emission) = texture(&self.emission, ColorSpace::Srgb)? {
+            principled = principled.with_emission(emission);
+        }
+        if let Some(metallic) = texture(&self.metallic, ColorSpace::Linear)? {
+            principled = principled.with_metallic(metallic);
+        }
+        if let Some(sheen) = texture(&self.sheen, ColorSpace::Linear)? {
+            principled = principled.with_sheen(sheen);
+        }
+        if let Some(clearcoat) = texture(&self.clearcoat, ColorSpace::Linear)? {
+            principled = principled.with_clearcoat(clearcoat);
+        }
+
+        // Without a PBR roughness, convert the Phong exponent through the usual
+        // Beckmann width sqrt(2 / (Ns + 2)), which is the GGX alpha, roughness
+        // squared.
+        match texture(&self.roughness, ColorSpace::Linear)? {
+            Some(roughness) => principled = principled.with_roughness(roughness),
+            None => {
+                if let Some(shininess) = self.shininess {
+                    let roughness = (2.0 / (shininess.max(0.0) + 2.0)).powf(0.25);
+                    principled = principled.with_roughness(constant(roughness));
+                }
+            }
+        }
+
+        let ior = self.ior.filter(|&ior| ior >= 1.0).unwrap_or(principled.ior);
+        let transmission = match self.dissolve {
+            Some(dissolve) if self.refractive() => 1.0 - dissolve,
+            _ => 0.0,
+        };
+
+        Ok(principled.with_transmission(constant(transmission), ior))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY: &str = "
+# Exported by hand.
+newmtl red plastic
+Kd 0.8 0.1 0.1
+Ks 0.5
+Ns 98
+illum 2
+
+newmtl glass
+Kd 1 1 1
+Ni 1.45
+d 0.1
+illum 4
+
+newmtl gold
+Kd 1.0 0.78 0.34
+Pm 1
+Pr 0.2
+
+newmtl net curtain
+Kd 0.9 0.9 0.9
+d 0.5
+illum 2
+";
+
+    fn scalar(texture: &Rc<dyn Texture>) -> f64 {
+        texture.value(0.0, 0.0, Vec3::default()).x()
+    }
+
+    #[test]
+    fn maps_classic_and_pbr_statements_onto_principled() {
+        let library = MtlLibrary::parse(LIBRARY, &SearchPath::new()).unwrap();
+        assert_eq!(
+            library.names().collect::<Vec<_>>(),
+            ["red plastic", "glass", "gold", "net curtain"]
+        );
+
+        let plastic = library.get("red plastic").unwrap();
+        let base = plastic.base_color.value(0.0, 0.0, Vec3::default());
+        assert_eq!((base.x(), base.y(), base.z()), (0.8, 0.1, 0.1));
+        assert_eq!(scalar(&plastic.specular), 0.5);
+        assert!((scalar(&plastic.roughness) - 0.02f64.powf(0.25)).abs() < 1e-12);
+        assert_eq!(scalar(&plastic.transmission), 0.0);
+
+        let glass = library.get("glass").unwrap();
+        assert_eq!(glass.ior, 1.45);
+        assert!((scalar(&glass.transmission) - 0.9).abs() < 1e-12);
+
+        let gold = library.get("gold").unwrap();
+        assert_eq!(scalar(&gold.metallic), 1.0);
+        assert_eq!(scalar(&gold.roughness), 0.2);
+    }
+
+    #[test]
+    fn dissolve_without_refraction_cuts_the_material_out() {
+        let library = MtlLibrary::parse(LIBRARY, &SearchPath::new()).unwrap();
+        let p = Vec3::default();
+
+        let curtain = library.get("net curtain").unwrap();
+        assert_eq!(scalar(&curtain.transmission), 0.0);
+        assert_eq!(
+            library
+                .material("net curtain")
+                .unwrap()
+                .opacity(0.0, 0.0, p),
+            0.5
+        );
+
+        // Glass turns its dissolve into transmission instead.
+        assert_eq!(library.material("glass").unwrap().opacity(0.0, 0.0, p), 1.0);
+        assert_eq!(
+            library
+                .material("red plastic")
+                .unwrap()
+                .opacity(0.0, 0.0, p),
+            1.0
+        );
+    }
+
+    #[test]
+    fn reports_bad_numbers_and_missing_maps() {
+        match MtlLibrary::parse("newmtl a\nKd 0.5 x 0.5\n", &SearchPath::new()) {
+            Err(MtlError::Syntax { line: 2, .. }) => {}
+            other => panic!("expected a syntax error, got {:?}", other.err()),
+        }
+
+        match MtlLibrary::parse("newmtl a\nmap_Kd -bm 1 missing.png\n", &SearchPath::new()) {
+            Err(MtlError::Texture { material, .. }) => assert_eq!(material, "a"),
+            other => panic!("expected a texture error, got {:?}", other.err()),
+        }
+    }
+}
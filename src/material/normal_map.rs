@@ -0,0 +1,210 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::texture::{ColorSpace, ImageTexture, Texture};
+use crate::vec3::{Color, Vec3};
+
+// The hit as the wrapped material should see it, with its shading normal
+// replaced. Normals that would face away from the incoming ray are ignored, as
+// they'd leave the surface black.
+fn with_normal(normal: Vec3, ray_in: &Ray, record: &HitRecord) -> HitRecord {
+    if Vec3::dot(normal, ray_in.direction()) >= 0.0 {
+        return record.clone();
+    }
+
+    HitRecord {
+        normal,
+        ..record.clone()
+    }
+}
+
+// Perturbs the shading normal of `material` with a tangent-space normal map, where
+// red, green and blue in [0, 1] encode the x (along dp/du), y (along dp/dv) and z
+// (along the normal) components in [-1, 1].
+pub struct NormalMapped {
+    pub material: Rc<dyn Material>,
+    pub normal_map: Rc<dyn Texture>,
+}
+
+impl NormalMapped {
+    // Normal maps store vectors, not colours, so the image is read as linear
+    // whatever colour space it was loaded in.
+    pub fn new(material: Rc<dyn Material>, normal_map: ImageTexture) -> Self {
+        let normal_map = normal_map.with_color_space(ColorSpace::Linear);
+        Self::from_texture(material, Rc::new(normal_map))
+    }
+
+    // Uses `normal_map` as is, for textures whose values are already linear.
+    pub fn from_texture(material: Rc<dyn Material>, normal_map: Rc<dyn Texture>) -> Self {
+        NormalMapped {
+            material,
+            normal_map,
+        }
+    }
+
+    fn normal(&self, record: &HitRecord) -> Vec3 {
+        let n = record.normal;
+        if record.dpdu.length_squared() == 0.0 {
+            return n;
+        }
+
+        let tangent = Vec3::unit_vector(record.dpdu - Vec3::dot(record.dpdu, n) * n);
+        let mut bitangent = Vec3::cross(n, tangent);
+        if Vec3::dot(bitangent, record.dpdv) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        let texel = self.normal_map.value_at(record);
+        let local = 2.0 * texel - Vec3(1.0, 1.0, 1.0);
+
+        Vec3::unit_vector(local.x() * tangent + local.y() * bitangent + local.z() * n)
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material
+            .scatter(ray_in, &record, attenuation, scattered)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material.eval(ray_in, &record, scattered)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material.pdf(ray_in, &record, scattered)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        self.material.emitted(ray_in, record)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        self.material.opacity(u, v, p)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        self.material.albedo(record)
+    }
+}
+
+// Step in (u, v) for the finite differences of the height field.
+const BUMP_DELTA: f64 = 0.0005;
+
+// Perturbs the shading normal of `material` as if the surface were displaced
+// along its normal by the luminance of `height` times `scale`. The height is
+// sampled at nearby (u, v) and points, so both image and solid textures like
+// `NoiseTexture` work.
+pub struct BumpMapped {
+    pub material: Rc<dyn Material>,
+    pub height: Rc<dyn Texture>,
+    pub scale: f64,
+}
+
+impl BumpMapped {
+    // Like normal maps, height maps are data and are read as linear.
+    pub fn new(material: Rc<dyn Material>, height: ImageTexture, scale: f64) -> Self {
+        let height = height.with_color_space(ColorSpace::Linear);
+        Self::from_texture(material, Rc::new(height), scale)
+    }
+
+    pub fn from_texture(material: Rc<dyn Material>, height: Rc<dyn Texture>, scale: f64) -> Self {
+        BumpMapped {
+            material,
+            height,
+            scale,
+        }
+    }
+
+    fn normal(&self, record: &HitRecord) -> Vec3 {
+        let n = record.normal;
+        if record.dpdu.length_squared() == 0.0 || record.dpdv.length_squared() == 0.0 {
+            return n;
+        }
+
+        let height = |u: f64, v: f64, p: Vec3| self.scale * self.height.value(u, v, p).luminance();
+        let (u, v, p) = (record.u, record.v, record.p);
+
+        let h = height(u, v, p);
+        let dhdu = (height(u + BUMP_DELTA, v, p + BUMP_DELTA * record.dpdu) - h) / BUMP_DELTA;
+        let dhdv = (height(u, v + BUMP_DELTA, p + BUMP_DELTA * record.dpdv) - h) / BUMP_DELTA;
+
+        let dpdu = record.dpdu + dhdu * n;
+        let dpdv = record.dpdv + dhdv * n;
+
+        let bumped = Vec3::unit_vector(Vec3::cross(dpdu, dpdv));
+        if Vec3::dot(bumped, n) < 0.0 {
+            -bumped
+        } else {
+            bumped
+        }
+    }
+}
+
+impl Material for BumpMapped {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material
+            .scatter(ray_in, &record, attenuation, scattered)
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material.eval(ray_in, &record, scattered)
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let record = with_normal(self.normal(record), ray_in, record);
+        self.material.pdf(ray_in, &record, scattered)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        self.material.emitted(ray_in, record)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        self.material.opacity(u, v, p)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        self.material.albedo(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    #[test]
+    fn normal_maps_loaded_as_srgb_are_read_as_linear() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128, 128, 255])));
+        let texture = ImageTexture::from(&image);
+        assert_eq!(texture.color_space(), ColorSpace::Srgb);
+
+        let base = Rc::new(Lambertian::from((0.5, 0.5, 0.5)));
+        let mapped = NormalMapped::new(base, texture);
+        let texel = mapped.normal_map.value(0.5, 0.5, Vec3::default());
+
+        assert!((texel.x() - 128.0 / 255.0).abs() < 1e-6);
+        assert!((texel.z() - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,94 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+use crate::{degrees_to_radians, PI};
+
+// Rough diffuse reflection from the Oren-Nayar microfacet model. `sigma` is the
+// standard deviation of the facet slopes in degrees; 0 is `Lambertian`.
+pub struct OrenNayar {
+    pub albedo: Rc<dyn Texture>,
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Rc<dyn Texture>, sigma: f64) -> Self {
+        let sigma2 = degrees_to_radians(sigma).powi(2);
+
+        OrenNayar {
+            albedo,
+            a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    // The BRDF times pi, for local directions `wo` and `wi`.
+    fn weight(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let sin_theta_o = (1.0 - wo.z() * wo.z()).max(0.0).sqrt();
+        let sin_theta_i = (1.0 - wi.z() * wi.z()).max(0.0).sqrt();
+
+        let cos_phi_diff = if sin_theta_o > 1e-4 && sin_theta_i > 1e-4 {
+            (wo.x() * wi.x() + wo.y() * wi.y()) / (sin_theta_o * sin_theta_i)
+        } else {
+            0.0
+        };
+
+        // sin(alpha) * tan(beta), with alpha the larger polar angle and beta the
+        // smaller.
+        let (sin_alpha, tan_beta) = if wi.z().abs() > wo.z().abs() {
+            (sin_theta_o, sin_theta_i / wi.z().abs())
+        } else {
+            (sin_theta_i, sin_theta_o / wo.z().abs().max(1e-4))
+        };
+
+        self.a + self.b * cos_phi_diff.max(0.0) * sin_alpha * tan_beta
+    }
+
+    fn local_directions(ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> (Vec3, Vec3) {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = onb.to_local(Vec3::unit_vector(scattered.direction()));
+        (wo, wi)
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = Vec3::rand_cosine_direction();
+
+        // Cosine sampling cancels the 1/pi and the cosine term.
+        *attenuation = self.weight(wo, wi) * self.albedo.value_at(record);
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let (wo, wi) = Self::local_directions(ray_in, record, scattered);
+        if wi.z() <= 0.0 {
+            return Some(Vec3(0.0, 0.0, 0.0));
+        }
+        Some(self.weight(wo, wi) * wi.z() / PI * self.albedo.value_at(record))
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let (_, wi) = Self::local_directions(ray_in, record, scattered);
+        Some(wi.z().max(0.0) / PI)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        Some(self.albedo.value_at(record))
+    }
+}
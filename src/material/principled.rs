@@ -0,0 +1,190 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, random_double};
+
+const CLEARCOAT_ROUGHNESS: f64 = 0.1;
+const CLEARCOAT_IOR: f64 = 1.5;
+
+// A Disney-style uber material. Every parameter is a texture; scalar parameters
+// read the luminance of their texture and are clamped to [0, 1].
+//
+// Lobes are picked at random in proportion to their weights: the clearcoat
+// first, then metal, glass or a diffuse base under a dielectric specular layer.
+pub struct Principled {
+    pub base_color: Rc<dyn Texture>,
+    pub metallic: Rc<dyn Texture>,
+    pub roughness: Rc<dyn Texture>,
+    // Scales the base's specular reflectance at normal incidence from 0 to 0.08.
+    pub specular: Rc<dyn Texture>,
+    pub clearcoat: Rc<dyn Texture>,
+    pub sheen: Rc<dyn Texture>,
+    pub transmission: Rc<dyn Texture>,
+    pub emission: Rc<dyn Texture>,
+    pub ior: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: Rc<dyn Texture>) -> Self {
+        let constant = |value: f64| Rc::new(SolidColor::from(value)) as Rc<dyn Texture>;
+
+        Principled {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            clearcoat: constant(0.0),
+            sheen: constant(0.0),
+            transmission: constant(0.0),
+            emission: constant(0.0),
+            ior: 1.5,
+        }
+    }
+
+    pub fn with_metallic(self, metallic: Rc<dyn Texture>) -> Self {
+        Principled { metallic, ..self }
+    }
+
+    pub fn with_roughness(self, roughness: Rc<dyn Texture>) -> Self {
+        Principled { roughness, ..self }
+    }
+
+    pub fn with_specular(self, specular: Rc<dyn Texture>) -> Self {
+        Principled { specular, ..self }
+    }
+
+    pub fn with_clearcoat(self, clearcoat: Rc<dyn Texture>) -> Self {
+        Principled { clearcoat, ..self }
+    }
+
+    pub fn with_sheen(self, sheen: Rc<dyn Texture>) -> Self {
+        Principled { sheen, ..self }
+    }
+
+    pub fn with_transmission(self, transmission: Rc<dyn Texture>, ior: f64) -> Self {
+        Principled {
+            transmission,
+            ior,
+            ..self
+        }
+    }
+
+    pub fn with_emission(self, emission: Rc<dyn Texture>) -> Self {
+        Principled { emission, ..self }
+    }
+
+    // Picks a lobe and samples a local direction from it, returning the direction
+    // and its throughput weight.
+    fn sample(&self, wo: Vec3, record: &HitRecord) -> Option<(Vec3, Color)> {
+        let (u, v, p) = (record.u, record.v, record.p);
+        let scalar =
+            |texture: &Rc<dyn Texture>| clamp(texture.value(u, v, p).luminance(), 0.0, 1.0);
+        let white = Vec3(1.0, 1.0, 1.0);
+
+        // Light the clearcoat doesn't reflect carries on into the layers below.
+        let clearcoat = scalar(&self.clearcoat);
+        if clearcoat > 0.0 {
+            let ggx = Ggx::new(CLEARCOAT_ROUGHNESS);
+            let m = ggx.sample_visible_normal(wo);
+            let fresnel = microfacet::fresnel_dielectric(Vec3::dot(wo, m), CLEARCOAT_IOR);
+            if random_double() < clearcoat * fresnel {
+                return specular_reflection(&ggx, wo, m).map(|(wi, weight)| (wi, weight * white));
+            }
+        }
+
+        let base_color = self.base_color.value_at(record);
+        let metallic = scalar(&self.metallic);
+        let transmission = (1.0 - metallic) * scalar(&self.transmission);
+
+        let ggx = Ggx::new(scalar(&self.roughness));
+        let m = ggx.sample_visible_normal(wo);
+        let cos_m = Vec3::dot(wo, m);
+
+        let lobe = random_double();
+        if lobe < metallic {
+            let fresnel = microfacet::fresnel_schlick(cos_m, base_color);
+            specular_reflection(&ggx, wo, m).map(|(wi, weight)| (wi, weight * fresnel))
+        } else if lobe < metallic + transmission {
+            let eta = if record.front_face {
+                self.ior
+            } else {
+                1.0 / self.ior
+            };
+
+            match microfacet::refract(wo, m, eta) {
+                Some(wi) if random_double() >= microfacet::fresnel_dielectric(cos_m, eta) => {
+                    if wi.z() >= 0.0 {
+                        return None;
+                    }
+                    Some((wi, ggx.g2(wo, wi) / ggx.g1(wo) * base_color))
+                }
+                _ => specular_reflection(&ggx, wo, m).map(|(wi, weight)| (wi, weight * white)),
+            }
+        } else {
+            let f0 = 0.08 * scalar(&self.specular);
+            let eta = (1.0 + f0.sqrt()) / (1.0 - f0.sqrt());
+            if random_double() < microfacet::fresnel_dielectric(cos_m, eta) {
+                return specular_reflection(&ggx, wo, m).map(|(wi, weight)| (wi, weight * white));
+            }
+
+            let wi = Vec3::rand_cosine_direction();
+            let h = Vec3::unit_vector(wo + wi);
+            let sheen = scalar(&self.sheen) * (1.0 - Vec3::dot(wi, h)).max(0.0).powi(5);
+            Some((wi, base_color + sheen))
+        }
+    }
+}
+
+fn specular_reflection(ggx: &Ggx, wo: Vec3, m: Vec3) -> Option<(Vec3, f64)> {
+    let wi = microfacet::reflect(wo, m);
+    if wi.z() <= 0.0 {
+        return None;
+    }
+
+    Some((wi, ggx.g2(wo, wi) / ggx.g1(wo)))
+}
+
+impl From<Color> for Principled {
+    fn from(color: Color) -> Self {
+        Principled::new(Rc::new(SolidColor::new(color)))
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        match self.sample(wo, record) {
+            Some((wi, weight)) => {
+                *attenuation = weight;
+                *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn emitted(&self, _ray_in: &Ray, record: &HitRecord) -> Color {
+        self.emission.value_at(record)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        Some(self.base_color.value_at(record))
+    }
+}
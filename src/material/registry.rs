@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, error::Error, fmt, rc::Rc};
+
+use super::{
+    AnisotropicMetal, Dielectric, DiffuseLight, Lambertian, Material, Metal, OrenNayar, Principled,
+    RoughDielectric, Sheen, Subsurface, ThinFilm,
+};
+use crate::texture::{ImageTexture, SolidColor, Texture, Wrap};
+use crate::vec3::Vec3;
+
+#[derive(Debug)]
+pub enum RegistryError {
+    UnknownMaterial(String),
+    DuplicateName(String),
+    // A factory failed, usually because an asset it needed didn't load.
+    Build { name: String, error: Box<dyn Error> },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownMaterial(name) => write!(f, "no material named {:?}", name),
+            RegistryError::DuplicateName(name) => {
+                write!(f, "a material named {:?} is already registered", name)
+            }
+            RegistryError::Build { name, error } => {
+                write!(f, "could not build material {:?}: {}", name, error)
+            }
+        }
+    }
+}
+
+impl Error for RegistryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RegistryError::Build { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+type Factory = Box<dyn Fn() -> Result<Rc<dyn Material>, Box<dyn Error>>>;
+
+// Wraps a constructor that can't fail as a `Factory`.
+fn preset<M, F>(build: F) -> Factory
+where
+    M: Material + 'static,
+    F: Fn() -> M + 'static,
+{
+    Box::new(move || Ok(Rc::new(build()) as Rc<dyn Material>))
+}
+
+enum Entry {
+    Built(Rc<dyn Material>),
+    Factory(Factory),
+}
+
+// Materials by name, so scenes can share one instance of "glass" rather than
+// each building their own. Factories run the first time their name is looked up,
+// which keeps presets that load files from costing anything until used.
+#[derive(Default)]
+pub struct MaterialRegistry {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl MaterialRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_presets() -> Self {
+        let mut registry = Self::new();
+        let solid = |r, g, b| -> Rc<dyn Texture> { Rc::new(SolidColor::new(Vec3(r, g, b))) };
+        let scalar = |value: f64| -> Rc<dyn Texture> { Rc::new(SolidColor::from(value)) };
+
+        let presets: Vec<(&str, Factory)> = vec![
+            ("gold", preset(|| Metal::gold(0.1))),
+            ("copper", preset(|| Metal::copper(0.15))),
+            ("aluminium", preset(|| Metal::aluminium(0.2))),
+            (
+                "brushed-aluminium",
+                preset(|| AnisotropicMetal::brushed_aluminium(0.05, 0.35)),
+            ),
+            (
+                "mirror",
+                preset(|| Metal::rough(Vec3(0.95, 0.95, 0.95), 0.0)),
+            ),
+            ("bronze", preset(|| Metal::rough(Vec3(0.7, 0.6, 0.5), 0.0))),
+            ("glass", preset(|| Dielectric::new(1.5))),
+            ("frosted-glass", preset(|| RoughDielectric::new(1.5, 0.3))),
+            ("water", preset(|| Dielectric::new(1.333))),
+            ("diamond", preset(|| Dielectric::new(2.42))),
+            ("clay", preset(|| Lambertian::from((0.4, 0.2, 0.1)))),
+            (
+                "checker",
+                preset(|| Lambertian::from((Vec3(0.2, 0.3, 0.1), Vec3(0.9, 0.9, 0.9)))),
+            ),
+            (
+                "chalk",
+                preset(move || OrenNayar::new(solid(0.9, 0.9, 0.88), 30.0)),
+            ),
+            (
+                "plastic",
+                preset(move || Principled::new(solid(0.8, 0.1, 0.1)).with_roughness(scalar(0.3))),
+            ),
+            (
+                "rubber",
+                preset(move || {
+                    Principled::new(solid(0.05, 0.05, 0.05)).with_roughness(scalar(0.8))
+                }),
+            ),
+            (
+                "marble",
+                preset(move || Subsurface::new(solid(0.95, 0.93, 0.9), 0.5, 1.5)),
+            ),
+            (
+                "velvet",
+                preset(move || {
+                    let base = Rc::new(Lambertian::new(solid(0.3, 0.02, 0.05)));
+                    Sheen::new(base, solid(1.0, 0.4, 0.5), 0.4)
+                }),
+            ),
+            ("soap-bubble", preset(|| ThinFilm::soap_bubble(400.0))),
+            (
+                "earth",
+                Box::new(|| {
+                    // Longitude wraps round, so the seam at u = 0 blends across.
+                    let map = ImageTexture::new("earthmap.jpg")?.with_wrap_u(Wrap::Repeat);
+                    Ok(Rc::new(Lambertian::new(Rc::new(map))))
+                }),
+            ),
+            ("light", preset(|| DiffuseLight::from((4.0, 4.0, 4.0)))),
+        ];
+
+        for (name, factory) in presets {
+            registry
+                .entries
+                .insert(name.to_string(), Entry::Factory(factory));
+        }
+
+        registry
+    }
+
+    pub fn register(
+        &mut self,
+        name: &str,
+        material: Rc<dyn Material>,
+    ) -> Result<(), RegistryError> {
+        self.insert(name, Entry::Built(material))
+    }
+
+    // Adds a material built on first use. A factory that fails is tried again on
+    // the next lookup.
+    pub fn register_factory<F>(&mut self, name: &str, factory: F) -> Result<(), RegistryError>
+    where
+        F: Fn() -> Result<Rc<dyn Material>, Box<dyn Error>> + 'static,
+    {
+        self.insert(name, Entry::Factory(Box::new(factory)))
+    }
+
+    // Replaces the material for `name`, or adds it. Materials already handed out
+    // keep the old instance.
+    pub fn replace(&mut self, name: &str, material: Rc<dyn Material>) {
+        self.entries
+            .insert(name.to_string(), Entry::Built(material));
+    }
+
+    pub fn get(&mut self, name: &str) -> Result<Rc<dyn Material>, RegistryError> {
+        let entry = self
+            .entries
+            .get_mut(name)
+            .ok_or_else(|| RegistryError::UnknownMaterial(name.to_string()))?;
+
+        let material = match entry {
+            Entry::Built(material) => material.clone(),
+            Entry::Factory(factory) => {
+                let material = factory().map_err(|error| RegistryError::Build {
+                    name: name.to_string(),
+                    error,
+                })?;
+                *entry = Entry::Built(material.clone());
+                material
+            }
+        };
+
+        Ok(material)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    // Registered names in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    fn insert(&mut self, name: &str, entry: Entry) -> Result<(), RegistryError> {
+        if self.contains(name) {
+            return Err(RegistryError::DuplicateName(name.to_string()));
+        }
+
+        self.entries.insert(name.to_string(), entry);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+    use crate::{random_scene_objects, simple_light};
+
+    #[test]
+    fn scenes_built_from_one_registry_share_its_materials() {
+        let mut registry = MaterialRegistry::with_presets();
+        let glass = registry.get("glass").unwrap();
+        assert!(Rc::ptr_eq(&glass, &registry.get("glass").unwrap()));
+
+        // The big glass sphere in one, the light above the other.
+        let scene = random_scene_objects(&mut registry).unwrap();
+        let down = Ray::new(Vec3(0.0, 10.0, 0.0), Vec3(0.0, -1.0, 0.0), 0.0);
+        let record = scene.hit(down, 0.001, f64::INFINITY).unwrap();
+        assert!(Rc::ptr_eq(&record.mat_ptr, &glass));
+
+        let light = registry.get("light").unwrap();
+        let lit = simple_light(&mut registry).unwrap();
+        let down = Ray::new(Vec3(0.0, 20.0, 0.0), Vec3(0.0, -1.0, 0.0), 0.0);
+        let record = lit.hit(down, 0.001, f64::INFINITY).unwrap();
+        assert!(Rc::ptr_eq(&record.mat_ptr, &light));
+
+        match registry.register("glass", Rc::new(Dielectric::new(1.4))) {
+            Err(RegistryError::DuplicateName(name)) => assert_eq!(name, "glass"),
+            other => panic!("expected a duplicate name, got {:?}", other),
+        }
+        match registry.get("unobtainium") {
+            Err(RegistryError::UnknownMaterial(name)) => assert_eq!(name, "unobtainium"),
+            other => panic!("expected an unknown material, got {:?}", other.err()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::microfacet::Charlie;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, random_double, PI};
+
+// A soft, grazing-angle sheen layered over another material, for cloth and
+// velvet. The sheen is added to the base, as in the Disney model, and each
+// scatter picks one of the two at random.
+pub struct Sheen {
+    pub base: Rc<dyn Material>,
+    pub color: Rc<dyn Texture>,
+    pub roughness: f64,
+}
+
+impl Sheen {
+    pub fn new(base: Rc<dyn Material>, color: Rc<dyn Texture>, roughness: f64) -> Self {
+        Sheen {
+            base,
+            color,
+            roughness: clamp(roughness, 0.0, 1.0),
+        }
+    }
+
+    // The chance of sampling the sheen rather than the base.
+    fn probability(color: Color) -> f64 {
+        0.5 * clamp(color.luminance(), 0.0, 1.0)
+    }
+
+    // The sheen BRDF for local directions `wo` and `wi`.
+    fn brdf(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let h = Vec3::unit_vector(wo + wi);
+        let charlie = Charlie::new(self.roughness);
+        charlie.d(h) * charlie.visibility(wo, wi)
+    }
+}
+
+impl Material for Sheen {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let color = self.color.value_at(record);
+
+        // Spend fewer samples on faint sheen.
+        let probability = Self::probability(color);
+        if random_double() >= probability {
+            if !self.base.scatter(ray_in, record, attenuation, scattered) {
+                return false;
+            }
+            *attenuation /= 1.0 - probability;
+            return true;
+        }
+
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        // Cosine sampling cancels the cosine term, leaving the BRDF times pi.
+        let wi = Vec3::rand_cosine_direction();
+
+        *attenuation = color * (self.brdf(wo, wi) * PI / probability);
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    // The base plus the sheen, which is only there when both directions are
+    // above the surface.
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let base = self.base.eval(ray_in, record, scattered)?;
+
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        let wi = onb.to_local(Vec3::unit_vector(scattered.direction()));
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Some(base);
+        }
+
+        Some(base + self.color.value_at(record) * (self.brdf(wo, wi) * wi.z()))
+    }
+
+    fn pdf(&self, ray_in: &Ray, record: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let base = self.base.pdf(ray_in, record, scattered)?;
+        let probability = Self::probability(self.color.value_at(record));
+
+        let cosine = Vec3::dot(record.normal, Vec3::unit_vector(scattered.direction()));
+        Some((1.0 - probability) * base + probability * cosine.max(0.0) / PI)
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        self.base.emitted(ray_in, record)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: Vec3) -> f64 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        self.base.albedo(record)
+    }
+}
@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::microfacet;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, random_double, PI};
+
+// Random-walk subsurface scattering inside a closed object, for skin, wax and
+// marble.
+//
+// Light refracts in through a smooth surface, then travels through the object by
+// the usual path tracing loop, which keeps long paths going by Russian roulette
+// rather than cutting them off at its bounce limit. Each time a path reaches the
+// inside of the surface, a free-flight distance is sampled; if that is shorter
+// than the distance it just travelled, the path scattered inside the object
+// before reaching the surface, and carries on from that point instead.
+//
+// `albedo` is the chance a path survives each scattering event, read at the point
+// of scattering so that solid textures vary through the volume. Paths take many
+// steps before leaving, so small differences between channels compound: a red
+// albedo slightly higher than blue gives skin its warm glow. `mean_free_path` is
+// the average distance between events, in scene units.
+pub struct Subsurface {
+    pub albedo: Rc<dyn Texture>,
+    pub mean_free_path: f64,
+    pub ior: f64,
+    // Henyey-Greenstein asymmetry, from -1 (back scattering) to 1 (forward).
+    pub anisotropy: f64,
+}
+
+impl Subsurface {
+    pub fn new(albedo: Rc<dyn Texture>, mean_free_path: f64, ior: f64) -> Self {
+        Subsurface {
+            albedo,
+            mean_free_path,
+            ior,
+            anisotropy: 0.0,
+        }
+    }
+
+    pub fn with_anisotropy(self, anisotropy: f64) -> Self {
+        Subsurface {
+            anisotropy: clamp(anisotropy, -0.99, 0.99),
+            ..self
+        }
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let direction = Vec3::unit_vector(ray_in.direction());
+        *attenuation = Vec3(1.0, 1.0, 1.0);
+
+        // The path travelled `distance` inside the object to get here. The free
+        // flight is sampled exactly, so the weight of a scattering event is just
+        // the albedo, and that of reaching the surface is 1.
+        if !record.front_face {
+            let distance = record.t * ray_in.direction().length();
+            let flight = -self.mean_free_path * (1.0 - random_double()).ln();
+
+            if flight < distance {
+                let p = ray_in.origin() + flight * direction;
+                *attenuation = self.albedo.value(record.u, record.v, p);
+                *scattered = Ray::new(
+                    p,
+                    sample_henyey_greenstein(direction, self.anisotropy),
+                    ray_in.time(),
+                );
+                return true;
+            }
+        }
+
+        // Reflect or refract at the smooth boundary, going in or out.
+        let wo = -direction;
+        let eta = if record.front_face {
+            self.ior
+        } else {
+            1.0 / self.ior
+        };
+        let fresnel = microfacet::fresnel_dielectric(Vec3::dot(wo, record.normal), eta);
+
+        let wi = match microfacet::refract(wo, record.normal, eta) {
+            Some(refracted) if random_double() >= fresnel => refracted,
+            _ => microfacet::reflect(wo, record.normal),
+        };
+        *scattered = Ray::new(record.p, wi, ray_in.time());
+        true
+    }
+
+    fn albedo(&self, record: &HitRecord) -> Option<Color> {
+        Some(self.albedo.value_at(record))
+    }
+}
+
+// Samples a new direction of travel about the current one from the
+// Henyey-Greenstein phase function with asymmetry `g`.
+fn sample_henyey_greenstein(direction: Vec3, g: f64) -> Vec3 {
+    let xi = random_double();
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * random_double();
+
+    Onb::build_from_w(direction).local(Vec3(
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    ))
+}
@@ -0,0 +1,145 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::microfacet::{self, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::{Color, Vec3};
+use crate::{clamp, random_double};
+
+// What the film is coated onto.
+#[derive(Debug, Copy, Clone)]
+pub enum Substrate {
+    // Glass-like, refracting whatever the film doesn't reflect. An index of 1 is a
+    // film with air on both sides, like a soap bubble.
+    Dielectric(f64),
+    Conductor { eta: Color, k: Color },
+}
+
+// A thin transparent film, such as soap or oil, over a substrate. Reflections off
+// the top and bottom of the film interfere, so its colour shifts with thickness
+// and viewing angle.
+pub struct ThinFilm {
+    // In nanometres, read from the luminance of the texture.
+    pub thickness: Rc<dyn Texture>,
+    pub film_ior: f64,
+    pub substrate: Substrate,
+    pub roughness: f64,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f64, film_ior: f64, substrate: Substrate) -> Self {
+        ThinFilm {
+            thickness: Rc::new(SolidColor::from(thickness)),
+            film_ior,
+            substrate,
+            roughness: 0.0,
+        }
+    }
+
+    pub fn soap_bubble(thickness: f64) -> Self {
+        Self::new(thickness, 1.33, Substrate::Dielectric(1.0))
+    }
+
+    pub fn oil_slick(thickness: f64) -> Self {
+        Self::new(thickness, 1.47, Substrate::Dielectric(1.33))
+    }
+
+    pub fn with_thickness(self, thickness: Rc<dyn Texture>) -> Self {
+        ThinFilm { thickness, ..self }
+    }
+
+    pub fn with_roughness(self, roughness: f64) -> Self {
+        ThinFilm {
+            roughness: clamp(roughness, 0.0, 1.0),
+            ..self
+        }
+    }
+
+    fn reflectance(&self, cos_theta: f64, record: &HitRecord) -> Color {
+        let thickness = self.thickness.value_at(record).luminance();
+
+        match self.substrate {
+            // The film is on the outside of the substrate, so a ray leaving a
+            // dielectric crosses it the other way round: from the substrate,
+            // through the film, into air.
+            Substrate::Dielectric(ior) if !record.front_face => microfacet::fresnel_thin_film(
+                cos_theta,
+                ior,
+                self.film_ior,
+                thickness,
+                Vec3(1.0, 1.0, 1.0),
+                Vec3::default(),
+            ),
+            Substrate::Dielectric(ior) => microfacet::fresnel_thin_film(
+                cos_theta,
+                1.0,
+                self.film_ior,
+                thickness,
+                Vec3(ior, ior, ior),
+                Vec3::default(),
+            ),
+            Substrate::Conductor { eta, k } => {
+                microfacet::fresnel_thin_film(cos_theta, 1.0, self.film_ior, thickness, eta, k)
+            }
+        }
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        record: &HitRecord,
+        attenuation: &mut Vec3,
+        scattered: &mut Ray,
+    ) -> bool {
+        let onb = Onb::build_from_w(record.normal);
+        let wo = onb.to_local(-Vec3::unit_vector(ray_in.direction()));
+        if wo.z() <= 0.0 {
+            return false;
+        }
+
+        let ggx = Ggx::new(self.roughness);
+        let m = ggx.sample_visible_normal(wo);
+        let cos_m = Vec3::dot(wo, m);
+        let reflectance = self.reflectance(cos_m, record);
+
+        // For a dielectric, reflect or refract in proportion to the average
+        // reflectance and reweight each channel to match.
+        let white = Vec3(1.0, 1.0, 1.0);
+        let (wi, weight, transmitted) = match self.substrate {
+            Substrate::Dielectric(ior) => {
+                let eta = if record.front_face { ior } else { 1.0 / ior };
+                let average = (reflectance.x() + reflectance.y() + reflectance.z()) / 3.0;
+
+                match microfacet::refract(wo, m, eta) {
+                    Some(wi) if random_double() >= average => {
+                        (wi, (white - reflectance) / (1.0 - average), true)
+                    }
+                    Some(_) => (microfacet::reflect(wo, m), reflectance / average, false),
+                    None => (microfacet::reflect(wo, m), white, false),
+                }
+            }
+            Substrate::Conductor { .. } => (microfacet::reflect(wo, m), reflectance, false),
+        };
+
+        if transmitted != (wi.z() < 0.0) {
+            return false;
+        }
+
+        *attenuation = weight * (ggx.g2(wo, wi) / ggx.g1(wo));
+        *scattered = Ray::new(record.p, onb.local(wi), ray_in.time());
+        true
+    }
+
+    // The substrate's colour; the film's own colours depend on the view.
+    fn albedo(&self, _record: &HitRecord) -> Option<Color> {
+        match self.substrate {
+            Substrate::Dielectric(_) => Some(Vec3(1.0, 1.0, 1.0)),
+            Substrate::Conductor { eta, k } => Some(microfacet::fresnel_conductor(1.0, eta, k)),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::rc::Rc;
+
+use super::Material;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::toon::quantise;
+use crate::vec3::{Color, Vec3};
+
+// Cel shading: diffuse light from one fixed direction, snapped to a few flat
+// bands, plus an optional hard rim where the surface turns away from the viewer.
+// The shading is emitted rather than scattered, so it looks the same under the
+// path tracer as under the toon integrator and ignores the scene's lights.
+pub struct Toon {
+    pub color: Rc<dyn Texture>,
+    // Points from the surface towards the light.
+    pub light_direction: Vec3,
+    pub bands: u32,
+    // Brightness of the darkest band.
+    pub shadow: f64,
+    pub rim_color: Color,
+    // How far in from the silhouette the rim reaches, from 0 (none) to 1.
+    pub rim_width: f64,
+}
+
+impl Toon {
+    pub fn new(color: Rc<dyn Texture>) -> Self {
+        Toon {
+            color,
+            light_direction: Vec3::unit_vector(Vec3(1.0, 2.0, 1.0)),
+            bands: 3,
+            shadow: 0.3,
+            rim_color: Vec3(1.0, 1.0, 1.0),
+            rim_width: 0.0,
+        }
+    }
+
+    pub fn with_light(self, direction: Vec3) -> Self {
+        Toon {
+            light_direction: Vec3::unit_vector(direction),
+            ..self
+        }
+    }
+
+    pub fn with_bands(self, bands: u32, shadow: f64) -> Self {
+        Toon {
+            bands,
+            shadow,
+            ..self
+        }
+    }
+
+    pub fn with_rim(self, color: Color, width: f64) -> Self {
+        Toon {
+            rim_color: color,
+            rim_width: width,
+            ..self
+        }
+    }
+}
+
+impl Material for Toon {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _record: &HitRecord,
+        _attenuation: &mut Vec3,
+        _scattered: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        let diffuse = Vec3::dot(record.normal, self.light_direction);
+        let brightness = self.shadow + (1.0 - self.shadow) * quantise(diffuse, self.bands);
+        let color = brightness * self.color.value_at(record);
+
+        let facing = Vec3::dot(record.normal, -Vec3::unit_vector(ray_in.direction()));
+        if facing < self.rim_width {
+            self.rim_color
+        } else {
+            color
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::vec3::{Color, Vec3};
+use crate::{random_double, PI};
+
+// All directions here are in the local shading frame, with the surface normal
+// along +z and both directions pointing away from the surface.
+
+/// The GGX (Trowbridge-Reitz) microfacet distribution.
+#[derive(Debug, Copy, Clone)]
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl Ggx {
+    /// Perceptual roughness in [0, 1] is squared to get the distribution width.
+    pub fn new(roughness: f64) -> Self {
+        Self::anisotropic(roughness, roughness)
+    }
+
+    pub fn anisotropic(roughness_x: f64, roughness_y: f64) -> Self {
+        let alpha = |roughness: f64| f64::max(roughness * roughness, 1e-4);
+        Ggx {
+            alpha_x: alpha(roughness_x),
+            alpha_y: alpha(roughness_y),
+        }
+    }
+
+    pub fn d(&self, m: Vec3) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let e = x * x + y * y + m.z() * m.z();
+
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    fn lambda(&self, w: Vec3) -> f64 {
+        if w.z() == 0.0 {
+            return f64::INFINITY;
+        }
+
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        let tan2 = (x * x + y * y) / (w.z() * w.z());
+
+        0.5 * (-1.0 + (1.0 + tan2).sqrt())
+    }
+
+    pub fn g1(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Height-correlated masking-shadowing.
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal from the distribution of normals visible from
+    /// `wo` (Heitz 2018).
+    pub fn sample_visible_normal(&self, wo: Vec3) -> Vec3 {
+        let vh = Vec3::unit_vector(Vec3(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()));
+
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0.0 {
+            Vec3(-vh.y(), vh.x(), 0.0) / len_sq.sqrt()
+        } else {
+            Vec3(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(vh, t1);
+
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + f64::max(0.0, 1.0 - p1 * p1 - p2 * p2).sqrt() * vh;
+
+        Vec3::unit_vector(Vec3(
+            self.alpha_x * nh.x(),
+            self.alpha_y * nh.y(),
+            f64::max(1e-6, nh.z()),
+        ))
+    }
+}
+
+/// The "Charlie" sheen distribution (Estevez and Kulla 2017), whose facets lie
+/// mostly flat against the surface like the fibres of velvet.
+#[derive(Debug, Copy, Clone)]
+pub struct Charlie {
+    alpha: f64,
+}
+
+impl Charlie {
+    pub fn new(roughness: f64) -> Self {
+        Charlie {
+            alpha: f64::max(roughness * roughness, 1e-3),
+        }
+    }
+
+    pub fn d(&self, m: Vec3) -> f64 {
+        let inv_alpha = 1.0 / self.alpha;
+        let sin2 = f64::max(0.0, 1.0 - m.z() * m.z());
+        (2.0 + inv_alpha) * sin2.powf(0.5 * inv_alpha) / (2.0 * PI)
+    }
+
+    /// The visibility term `G / (4 cos(wo) cos(wi))`, using Neubelt and Pettineo's
+    /// approximation.
+    pub fn visibility(&self, wo: Vec3, wi: Vec3) -> f64 {
+        let (cos_o, cos_i) = (wo.z(), wi.z());
+        1.0 / (4.0 * (cos_i + cos_o - cos_i * cos_o))
+    }
+}
+
+pub fn reflect(w: Vec3, m: Vec3) -> Vec3 {
+    -w + 2.0 * Vec3::dot(w, m) * m
+}
+
+pub fn fresnel_schlick(cos_theta: f64, f0: Color) -> Color {
+    let weight = (1.0 - cos_theta).max(0.0).powi(5);
+    f0 + weight * (Vec3(1.0, 1.0, 1.0) - f0)
+}
+
+/// Unpolarised Fresnel reflectance of a conductor with complex index of
+/// refraction `eta + i k`, per colour channel.
+pub fn fresnel_conductor(cos_theta: f64, eta: Color, k: Color) -> Color {
+    let channel = |eta: f64, k: f64| {
+        let cos2 = cos_theta * cos_theta;
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = f64::max(0.0, 0.5 * (a2_plus_b2 + t0)).sqrt();
+        let t2 = 2.0 * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+
+    Vec3(
+        channel(eta.x(), k.x()),
+        channel(eta.y(), k.y()),
+        channel(eta.z(), k.z()),
+    )
+}
+
+/// Refracts `w` through a surface with normal `m`, where `eta` is the index of
+/// refraction on the far side of the surface over the near side. Returns `None`
+/// on total internal reflection.
+pub fn refract(w: Vec3, m: Vec3, eta: f64) -> Option<Vec3> {
+    let cos_i = Vec3::dot(w, m);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * m)
+}
+
+/// Unpolarised Fresnel reflectance of a dielectric interface, with `eta` as in
+/// `refract`. Total internal reflection gives 1.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Wavelengths in nanometres used for the red, green and blue channels of
+// wavelength-dependent effects.
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 510.0, 475.0];
+
+/// Reflectance of a thin film of index `film_ior` and `thickness` nanometres
+/// between a medium of index `outer_ior`, where the light arrives from, and a
+/// substrate of complex index `eta + i k`. Light bouncing between the two faces
+/// of the film interferes with itself, which gives each channel a different
+/// reflectance (Airy summation).
+pub fn fresnel_thin_film(
+    cos_theta: f64,
+    outer_ior: f64,
+    film_ior: f64,
+    thickness: f64,
+    eta: Color,
+    k: Color,
+) -> Color {
+    let channel = |wavelength: f64, eta: f64, k: f64| {
+        let sin2 = Complex::real(1.0 - cos_theta * cos_theta);
+        let (n1, n2, n3) = (
+            Complex::real(outer_ior),
+            Complex::real(film_ior),
+            Complex::new(eta, k),
+        );
+
+        // Snell's law, with complex cosines for absorbing media and evanescent waves.
+        let cos1 = Complex::real(cos_theta);
+        let cos2 = (Complex::real(1.0) - n1 * n1 * sin2 / (n2 * n2)).sqrt();
+        let cos3 = (Complex::real(1.0) - n1 * n1 * sin2 / (n3 * n3)).sqrt();
+
+        let (r12_s, r12_p) = fresnel_amplitudes(n1, cos1, n2, cos2);
+        let (r23_s, r23_p) = fresnel_amplitudes(n2, cos2, n3, cos3);
+
+        // Phase difference between successive bounces inside the film.
+        let phase = Complex::real(4.0 * PI * thickness / wavelength) * n2 * cos2;
+        let shift = (Complex::new(0.0, 1.0) * phase).exp();
+
+        let airy = |r12: Complex, r23: Complex| {
+            let r = (r12 + r23 * shift) / (Complex::real(1.0) + r12 * r23 * shift);
+            r.norm_sqr()
+        };
+
+        0.5 * (airy(r12_s, r23_s) + airy(r12_p, r23_p))
+    };
+
+    Vec3(
+        channel(RGB_WAVELENGTHS[0], eta.x(), k.x()),
+        channel(RGB_WAVELENGTHS[1], eta.y(), k.y()),
+        channel(RGB_WAVELENGTHS[2], eta.z(), k.z()),
+    )
+}
+
+// Amplitude reflection coefficients for s and p polarised light going from index
+// `n_i` to `n_t`.
+fn fresnel_amplitudes(
+    n_i: Complex,
+    cos_i: Complex,
+    n_t: Complex,
+    cos_t: Complex,
+) -> (Complex, Complex) {
+    let s = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let p = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    (s, p)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+
+    fn real(re: f64) -> Self {
+        Complex { re, im: 0.0 }
+    }
+
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    fn exp(self) -> Self {
+        let magnitude = self.re.exp();
+        Complex::new(magnitude * self.im.cos(), magnitude * self.im.sin())
+    }
+
+    // The principal square root.
+    fn sqrt(self) -> Self {
+        let norm = self.norm_sqr().sqrt();
+        let re = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (norm - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Complex) -> Complex {
+        let denominator = rhs.norm_sqr();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denominator,
+            (self.im * rhs.re - self.re * rhs.im) / denominator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn conductor_fresnel_matches_the_normal_incidence_formula() {
+        let (eta, k) = (Vec3(0.143, 0.374, 1.442), Vec3(3.983, 2.385, 1.603));
+        let f = fresnel_conductor(1.0, eta, k);
+
+        for axis in 0..3 {
+            let (n, k) = (eta.get(axis), k.get(axis));
+            let expected = ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+            assert_close(f.get(axis), expected);
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel_without_absorption_is_the_dielectric_one() {
+        for &cos_theta in &[1.0, 0.8, 0.5, 0.2] {
+            let f = fresnel_conductor(cos_theta, Vec3(1.5, 1.5, 1.5), Vec3(0.0, 0.0, 0.0));
+            assert_close(f.x(), fresnel_dielectric(cos_theta, 1.5));
+        }
+    }
+
+    #[test]
+    fn conductor_fresnel_reflects_everything_at_grazing_angles() {
+        let f = fresnel_conductor(0.0, Vec3(0.2, 0.9, 1.1), Vec3(3.9, 2.4, 2.1));
+        for axis in 0..3 {
+            assert_close(f.get(axis), 1.0);
+        }
+    }
+
+    #[test]
+    fn a_film_of_no_thickness_leaves_the_bare_interface_from_either_side() {
+        let grey = |ior: f64| Vec3(ior, ior, ior);
+
+        for &cos_theta in &[1.0, 0.9, 0.6, 0.3] {
+            // Into glass from air, and out of glass into air, including angles
+            // past the critical one.
+            let entering = fresnel_thin_film(cos_theta, 1.0, 1.33, 0.0, grey(1.5), Vec3::default());
+            assert_close(entering.x(), fresnel_dielectric(cos_theta, 1.5));
+
+            let leaving = fresnel_thin_film(cos_theta, 1.5, 1.33, 0.0, grey(1.0), Vec3::default());
+            assert_close(leaving.x(), fresnel_dielectric(cos_theta, 1.0 / 1.5));
+        }
+    }
+}
@@ -68,6 +68,7 @@ impl Perlin {
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, p: Vec3) -> f64 {
         let u = p.x() - p.x().floor();
         let v = p.y() - p.y().floor();
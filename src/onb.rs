@@ -0,0 +1,79 @@
+use crate::vec3::Vec3;
+
+/// An orthonormal basis, used to move directions in and out of a shading frame
+/// whose `w` axis is the surface normal. Frames are right-handed: `u × v = w`.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let a = if w.x().abs() > 0.9 {
+            Vec3(0.0, 1.0, 0.0)
+        } else {
+            Vec3(1.0, 0.0, 0.0)
+        };
+        let v = Vec3::unit_vector(Vec3::cross(w, a));
+        let u = Vec3::cross(v, w);
+
+        Onb { u, v, w }
+    }
+
+    /// A frame around the normal `n` with `u` along the part of `tangent` that is
+    /// perpendicular to it, falling back to an arbitrary `u` if there is none.
+    pub fn from_normal_tangent(n: Vec3, tangent: Vec3) -> Self {
+        let w = Vec3::unit_vector(n);
+        let u = tangent - Vec3::dot(tangent, w) * w;
+        if u.length_squared() < 1e-12 {
+            return Self::build_from_w(w);
+        }
+
+        let u = Vec3::unit_vector(u);
+        Onb {
+            u,
+            v: Vec3::cross(w, u),
+            w,
+        }
+    }
+
+    /// Converts local coordinates (x along `u`, y along `v`, z along `w`) to world.
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    /// Converts a world direction into local coordinates.
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3(
+            Vec3::dot(a, self.u),
+            Vec3::dot(a, self.v),
+            Vec3::dot(a, self.w),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_right_handed(onb: Onb) {
+        let w = Vec3::cross(onb.u, onb.v);
+        assert!((w - onb.w).length() < 1e-12, "{:?}", onb);
+    }
+
+    #[test]
+    fn both_constructors_build_right_handed_frames() {
+        for &n in &[
+            Vec3(0.0, 0.0, 1.0),
+            Vec3(1.0, 0.0, 0.0),
+            Vec3(-0.3, 0.8, 0.2),
+        ] {
+            assert_right_handed(Onb::build_from_w(n));
+            assert_right_handed(Onb::from_normal_tangent(n, Vec3(0.0, 1.0, 0.0)));
+            assert_right_handed(Onb::from_normal_tangent(n, Vec3(0.2, -0.5, 0.7)));
+        }
+    }
+}
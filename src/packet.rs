@@ -0,0 +1,103 @@
+use crate::hittable::aabb::Aabb;
+use crate::ray::{InvRay, Ray};
+use crate::stats;
+use crate::vec3::Vec3;
+
+/// A bundle of coherent rays, such as the primary rays through one image tile,
+/// traced through the scene together.
+pub struct RayPacket {
+    rays: Vec<Ray>,
+    inv_rays: Vec<InvRay>,
+    frustum: Option<Frustum>,
+}
+
+impl RayPacket {
+    pub fn new(rays: Vec<Ray>, frustum: Option<Frustum>) -> Self {
+        let inv_rays = rays.iter().map(InvRay::from).collect();
+        RayPacket {
+            rays,
+            inv_rays,
+            frustum,
+        }
+    }
+
+    pub fn rays(&self) -> &[Ray] {
+        &self.rays
+    }
+
+    /// The rays with their reciprocal directions, computed once for the packet
+    /// so traversal doesn't redo them at every box.
+    pub fn inv_rays(&self) -> &[InvRay] {
+        &self.inv_rays
+    }
+
+    pub fn len(&self) -> usize {
+        self.rays.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rays.is_empty()
+    }
+
+    /// A volume that contains every ray in the packet, if one is known.
+    pub fn frustum(&self) -> Option<&Frustum> {
+        self.frustum.as_ref()
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Plane {
+    normal: Vec3,
+    offset: f64,
+}
+
+/// An open volume bounded by four planes. Points `p` with
+/// `dot(normal, p) + offset >= 0` for every plane are inside.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+    planes: [Plane; 4],
+}
+
+impl Frustum {
+    /// Builds a frustum from four planes, each given as a point on the plane and
+    /// two directions spanning it. `inside` is any point that must be inside the
+    /// frustum and is used to orient the planes.
+    pub fn new(planes: [(Vec3, Vec3, Vec3); 4], inside: Vec3) -> Self {
+        let plane = |(point, a, b): (Vec3, Vec3, Vec3)| {
+            let mut normal = Vec3::cross(a, b);
+            if Vec3::dot(normal, inside - point) < 0.0 {
+                normal = -normal;
+            }
+            Plane {
+                normal,
+                offset: -Vec3::dot(normal, point),
+            }
+        };
+
+        Frustum {
+            planes: [
+                plane(planes[0]),
+                plane(planes[1]),
+                plane(planes[2]),
+                plane(planes[3]),
+            ],
+        }
+    }
+
+    /// True if the box lies entirely outside the frustum, so no ray in the
+    /// packet can hit it.
+    pub fn culls(&self, bounding_box: &Aabb) -> bool {
+        stats::count_box_test();
+
+        self.planes.iter().any(|plane| {
+            // The corner of the box furthest along the plane normal.
+            let (min, max, n) = (bounding_box.min(), bounding_box.max(), plane.normal);
+            let p = Vec3(
+                if n.x() > 0.0 { max.x() } else { min.x() },
+                if n.y() > 0.0 { max.y() } else { min.y() },
+                if n.z() > 0.0 { max.z() } else { min.z() },
+            );
+            Vec3::dot(n, p) + plane.offset < 0.0
+        })
+    }
+}
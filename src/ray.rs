@@ -28,3 +28,32 @@ impl Ray {
         self.time
     }
 }
+
+// The rays through the neighbouring pixels to the right of and below a primary
+// ray, used to estimate how much of a surface the pixel covers. They're kept
+// apart from `Ray` so the rays traced through the accelerators stay small.
+#[derive(Debug, Copy, Clone)]
+pub struct RayDifferential {
+    pub rx: Ray,
+    pub ry: Ray,
+}
+
+// A ray with its reciprocal direction precomputed, so box slab tests can multiply
+// instead of divide.
+#[derive(Debug, Copy, Clone)]
+pub struct InvRay {
+    pub origin: Vec3,
+    pub dir: Vec3,
+    pub inv_dir: Vec3,
+}
+
+impl From<&Ray> for InvRay {
+    fn from(ray: &Ray) -> Self {
+        let dir = ray.direction();
+        InvRay {
+            origin: ray.origin(),
+            dir,
+            inv_dir: Vec3(1.0 / dir.x(), 1.0 / dir.y(), 1.0 / dir.z()),
+        }
+    }
+}
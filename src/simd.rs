@@ -0,0 +1,403 @@
+// Four-wide ray/box and up to eight-wide ray/triangle tests. With the `simd`
+// feature on x86_64 and a CPU that supports AVX these use `std::arch` intrinsics;
+// everywhere else they fall back to plain per-lane loops.
+
+use crate::hittable::aabb::Aabb;
+use crate::ray::InvRay;
+use crate::vec3::Vec3;
+
+pub const LANES: usize = 4;
+// AVX holds four `f64`s, so a full triangle packet is tested as two halves.
+pub const TRIANGLE_LANES: usize = 2 * LANES;
+
+/// Which implementation of the tests to run, found once when an accelerator is
+/// built rather than on every call.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Isa {
+    avx: bool,
+}
+
+impl Isa {
+    /// The fastest implementation this CPU supports.
+    pub fn detect() -> Self {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx") {
+                return Isa { avx: true };
+            }
+        }
+
+        Self::portable()
+    }
+
+    /// The plain per-lane loops, whatever the CPU.
+    pub fn portable() -> Self {
+        Isa { avx: false }
+    }
+
+    /// Slab test against four boxes at once. Returns each lane's entry distance
+    /// and a bit mask of the lanes that were hit within `[tmin, tmax]`.
+    pub fn hit_boxes4(
+        self,
+        bounds: &Bounds4,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> ([f64; LANES], u8) {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if self.avx {
+                return unsafe { avx::hit_boxes4(bounds, ray, tmin, tmax) };
+            }
+        }
+
+        portable::hit_boxes4(bounds, ray, tmin, tmax)
+    }
+
+    /// Möller–Trumbore against a packet of up to eight triangles. Returns the hit
+    /// distance per lane, or infinity where the lane was missed or fell outside
+    /// `(tmin, tmax)`. Packets of four or fewer cost a single four-wide test.
+    pub fn hit_triangles(
+        self,
+        triangles: &Triangle8,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> [f64; TRIANGLE_LANES] {
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        {
+            if self.avx {
+                return unsafe { avx::hit_triangles(triangles, ray, tmin, tmax) };
+            }
+        }
+
+        portable::hit_triangles(triangles, ray, tmin, tmax)
+    }
+}
+
+/// Four boxes in struct-of-arrays layout, indexed `[axis][lane]`.
+#[derive(Debug, Copy, Clone)]
+pub struct Bounds4 {
+    pub min: [[f64; LANES]; 3],
+    pub max: [[f64; LANES]; 3],
+}
+
+impl Default for Bounds4 {
+    fn default() -> Self {
+        Bounds4 {
+            min: [[f64::INFINITY; LANES]; 3],
+            max: [[f64::NEG_INFINITY; LANES]; 3],
+        }
+    }
+}
+
+impl Bounds4 {
+    pub fn set(&mut self, lane: usize, bounding_box: Aabb) {
+        for axis in 0..3 {
+            self.min[axis][lane] = bounding_box.min().get(axis);
+            self.max[axis][lane] = bounding_box.max().get(axis);
+        }
+    }
+}
+
+/// Up to eight triangles in struct-of-arrays layout, stored as a vertex and two
+/// edges. Unused lanes have zero-length edges, which the test always rejects.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Triangle8 {
+    pub v0: [[f64; TRIANGLE_LANES]; 3],
+    pub edge1: [[f64; TRIANGLE_LANES]; 3],
+    pub edge2: [[f64; TRIANGLE_LANES]; 3],
+    pub ids: [u32; TRIANGLE_LANES],
+    pub len: usize,
+}
+
+impl Triangle8 {
+    pub fn new(triangles: &[(u32, (Vec3, Vec3, Vec3))]) -> Self {
+        assert!(triangles.len() <= TRIANGLE_LANES);
+
+        let mut packet = Triangle8 {
+            len: triangles.len(),
+            ..Default::default()
+        };
+
+        for (lane, &(id, (v0, v1, v2))) in triangles.iter().enumerate() {
+            let edge1 = v1 - v0;
+            let edge2 = v2 - v0;
+
+            packet.ids[lane] = id;
+            for axis in 0..3 {
+                packet.v0[axis][lane] = v0.get(axis);
+                packet.edge1[axis][lane] = edge1.get(axis);
+                packet.edge2[axis][lane] = edge2.get(axis);
+            }
+        }
+
+        packet
+    }
+}
+
+const DET_EPSILON: f64 = 1e-12;
+
+mod portable {
+    use super::*;
+
+    pub fn hit_boxes4(bounds: &Bounds4, ray: &InvRay, tmin: f64, tmax: f64) -> ([f64; LANES], u8) {
+        let mut near = [tmin; LANES];
+        let mut far = [tmax; LANES];
+
+        for axis in 0..3 {
+            let origin = ray.origin.get(axis);
+            let inv_d = ray.inv_dir.get(axis);
+
+            for lane in 0..LANES {
+                let t0 = (bounds.min[axis][lane] - origin) * inv_d;
+                let t1 = (bounds.max[axis][lane] - origin) * inv_d;
+                near[lane] = f64::max(near[lane], f64::min(t0, t1));
+                far[lane] = f64::min(far[lane], f64::max(t0, t1));
+            }
+        }
+
+        let mut mask = 0;
+        for lane in 0..LANES {
+            if near[lane] < far[lane] {
+                mask |= 1 << lane;
+            }
+        }
+
+        (near, mask)
+    }
+
+    pub fn hit_triangles(
+        triangles: &Triangle8,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> [f64; TRIANGLE_LANES] {
+        let (o, d) = (ray.origin, ray.dir);
+        let mut hits = [f64::INFINITY; TRIANGLE_LANES];
+
+        for (lane, hit) in hits.iter_mut().enumerate().take(triangles.len) {
+            let e1 = [
+                triangles.edge1[0][lane],
+                triangles.edge1[1][lane],
+                triangles.edge1[2][lane],
+            ];
+            let e2 = [
+                triangles.edge2[0][lane],
+                triangles.edge2[1][lane],
+                triangles.edge2[2][lane],
+            ];
+
+            let pvec = [
+                d.y() * e2[2] - d.z() * e2[1],
+                d.z() * e2[0] - d.x() * e2[2],
+                d.x() * e2[1] - d.y() * e2[0],
+            ];
+            let det = e1[0] * pvec[0] + e1[1] * pvec[1] + e1[2] * pvec[2];
+            if det.abs() < DET_EPSILON {
+                continue;
+            }
+
+            let inv_det = 1.0 / det;
+            let tvec = [
+                o.x() - triangles.v0[0][lane],
+                o.y() - triangles.v0[1][lane],
+                o.z() - triangles.v0[2][lane],
+            ];
+
+            let u = (tvec[0] * pvec[0] + tvec[1] * pvec[1] + tvec[2] * pvec[2]) * inv_det;
+            let qvec = [
+                tvec[1] * e1[2] - tvec[2] * e1[1],
+                tvec[2] * e1[0] - tvec[0] * e1[2],
+                tvec[0] * e1[1] - tvec[1] * e1[0],
+            ];
+            let v = (d.x() * qvec[0] + d.y() * qvec[1] + d.z() * qvec[2]) * inv_det;
+            let t = (e2[0] * qvec[0] + e2[1] * qvec[1] + e2[2] * qvec[2]) * inv_det;
+
+            if u >= 0.0 && v >= 0.0 && u + v <= 1.0 && t > tmin && t < tmax {
+                *hit = t;
+            }
+        }
+
+        hits
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod avx {
+    use super::*;
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx")]
+    unsafe fn load(lanes: &[f64]) -> __m256d {
+        assert!(lanes.len() >= LANES);
+        _mm256_loadu_pd(lanes.as_ptr())
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn cross(a: [__m256d; 3], b: [__m256d; 3]) -> [__m256d; 3] {
+        [
+            _mm256_sub_pd(_mm256_mul_pd(a[1], b[2]), _mm256_mul_pd(a[2], b[1])),
+            _mm256_sub_pd(_mm256_mul_pd(a[2], b[0]), _mm256_mul_pd(a[0], b[2])),
+            _mm256_sub_pd(_mm256_mul_pd(a[0], b[1]), _mm256_mul_pd(a[1], b[0])),
+        ]
+    }
+
+    #[target_feature(enable = "avx")]
+    unsafe fn dot(a: [__m256d; 3], b: [__m256d; 3]) -> __m256d {
+        _mm256_add_pd(
+            _mm256_add_pd(_mm256_mul_pd(a[0], b[0]), _mm256_mul_pd(a[1], b[1])),
+            _mm256_mul_pd(a[2], b[2]),
+        )
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn hit_boxes4(
+        bounds: &Bounds4,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> ([f64; LANES], u8) {
+        let mut near = _mm256_set1_pd(tmin);
+        let mut far = _mm256_set1_pd(tmax);
+
+        for axis in 0..3 {
+            let origin = _mm256_set1_pd(ray.origin.get(axis));
+            let inv_d = _mm256_set1_pd(ray.inv_dir.get(axis));
+
+            let t0 = _mm256_mul_pd(_mm256_sub_pd(load(&bounds.min[axis]), origin), inv_d);
+            let t1 = _mm256_mul_pd(_mm256_sub_pd(load(&bounds.max[axis]), origin), inv_d);
+
+            near = _mm256_max_pd(near, _mm256_min_pd(t0, t1));
+            far = _mm256_min_pd(far, _mm256_max_pd(t0, t1));
+        }
+
+        let mask = _mm256_movemask_pd(_mm256_cmp_pd(near, far, _CMP_LT_OQ)) as u8;
+        let mut out = [0.0; LANES];
+        _mm256_storeu_pd(out.as_mut_ptr(), near);
+
+        (out, mask)
+    }
+
+    #[target_feature(enable = "avx")]
+    pub unsafe fn hit_triangles(
+        triangles: &Triangle8,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> [f64; TRIANGLE_LANES] {
+        let mut out = [f64::INFINITY; TRIANGLE_LANES];
+        for start in (0..triangles.len).step_by(LANES) {
+            let hits = hit_triangles4(triangles, start, ray, tmin, tmax);
+            _mm256_storeu_pd(out[start..].as_mut_ptr(), hits);
+        }
+
+        out
+    }
+
+    // Tests lanes `start..start + LANES` of the packet.
+    #[target_feature(enable = "avx")]
+    unsafe fn hit_triangles4(
+        triangles: &Triangle8,
+        start: usize,
+        ray: &InvRay,
+        tmin: f64,
+        tmax: f64,
+    ) -> __m256d {
+        let splat = |v: crate::vec3::Vec3| {
+            [
+                _mm256_set1_pd(v.x()),
+                _mm256_set1_pd(v.y()),
+                _mm256_set1_pd(v.z()),
+            ]
+        };
+        let soa = |lanes: &[[f64; TRIANGLE_LANES]; 3]| {
+            [
+                load(&lanes[0][start..]),
+                load(&lanes[1][start..]),
+                load(&lanes[2][start..]),
+            ]
+        };
+
+        let o = splat(ray.origin);
+        let d = splat(ray.dir);
+        let v0 = soa(&triangles.v0);
+        let e1 = soa(&triangles.edge1);
+        let e2 = soa(&triangles.edge2);
+
+        let pvec = cross(d, e2);
+        let det = dot(e1, pvec);
+        let inv_det = _mm256_div_pd(_mm256_set1_pd(1.0), det);
+
+        let tvec = [
+            _mm256_sub_pd(o[0], v0[0]),
+            _mm256_sub_pd(o[1], v0[1]),
+            _mm256_sub_pd(o[2], v0[2]),
+        ];
+        let u = _mm256_mul_pd(dot(tvec, pvec), inv_det);
+        let qvec = cross(tvec, e1);
+        let v = _mm256_mul_pd(dot(d, qvec), inv_det);
+        let t = _mm256_mul_pd(dot(e2, qvec), inv_det);
+
+        let zero = _mm256_setzero_pd();
+        let abs_det = _mm256_andnot_pd(_mm256_set1_pd(-0.0), det);
+
+        let mut mask = _mm256_cmp_pd(abs_det, _mm256_set1_pd(DET_EPSILON), _CMP_GE_OQ);
+        mask = _mm256_and_pd(mask, _mm256_cmp_pd(u, zero, _CMP_GE_OQ));
+        mask = _mm256_and_pd(mask, _mm256_cmp_pd(v, zero, _CMP_GE_OQ));
+        mask = _mm256_and_pd(
+            mask,
+            _mm256_cmp_pd(_mm256_add_pd(u, v), _mm256_set1_pd(1.0), _CMP_LE_OQ),
+        );
+        mask = _mm256_and_pd(mask, _mm256_cmp_pd(t, _mm256_set1_pd(tmin), _CMP_GT_OQ));
+        mask = _mm256_and_pd(mask, _mm256_cmp_pd(t, _mm256_set1_pd(tmax), _CMP_LT_OQ));
+
+        _mm256_blendv_pd(_mm256_set1_pd(f64::INFINITY), t, mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+
+    #[test]
+    fn detected_kernels_agree_with_the_portable_ones() {
+        let isa = Isa::detect();
+
+        for _ in 0..1000 {
+            let triangles: Vec<(u32, (Vec3, Vec3, Vec3))> = (0..TRIANGLE_LANES as u32)
+                .map(|id| {
+                    let v0 = Vec3::rand_with_range(-1.0, 1.0);
+                    let v1 = v0 + Vec3::rand_with_range(-1.0, 1.0);
+                    let v2 = v0 + Vec3::rand_with_range(-1.0, 1.0);
+                    (id, (v0, v1, v2))
+                })
+                .collect();
+            let ray = Ray::new(
+                Vec3::rand_with_range(-3.0, 3.0),
+                Vec3::rand_unit_vector(),
+                0.0,
+            );
+            let ray = InvRay::from(&ray);
+
+            // Odd lengths leave the second half of the packet partly empty.
+            for len in [3, 5, TRIANGLE_LANES] {
+                let packet = Triangle8::new(&triangles[..len]);
+                assert_eq!(
+                    isa.hit_triangles(&packet, &ray, 0.0, 10.0),
+                    portable::hit_triangles(&packet, &ray, 0.0, 10.0)
+                );
+            }
+
+            let mut bounds = Bounds4::default();
+            for (lane, &(_, (v0, _, _))) in triangles.iter().take(LANES).enumerate() {
+                bounds.set(lane, Aabb::new(v0, v0 + Vec3(0.5, 0.5, 0.5)));
+            }
+            assert_eq!(
+                isa.hit_boxes4(&bounds, &ray, 0.0, 10.0).1,
+                portable::hit_boxes4(&bounds, &ray, 0.0, 10.0).1
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+
+use crate::vec3::{Color, Vec3};
+
+// Whether the traversal counters are kept. They are only needed by the heatmap
+// debug render, so without the `stats` feature counting compiles to nothing.
+pub const ENABLED: bool = cfg!(feature = "stats");
+
+// Per-thread traversal counters. They are bumped by `Aabb::hit` and by BVH leaves
+// for every primitive they test, and read back by the heatmap debug render.
+thread_local! {
+    static BOX_TESTS: Cell<u64> = const { Cell::new(0) };
+    static PRIMITIVE_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TraversalCounts {
+    pub box_tests: u64,
+    pub primitive_tests: u64,
+}
+
+impl TraversalCounts {
+    pub fn total(&self) -> u64 {
+        self.box_tests + self.primitive_tests
+    }
+}
+
+#[inline(always)]
+pub fn count_box_test() {
+    count_box_tests(1);
+}
+
+#[inline(always)]
+pub fn count_box_tests(tests: u64) {
+    if ENABLED {
+        BOX_TESTS.with(|count| count.set(count.get() + tests));
+    }
+}
+
+#[inline(always)]
+pub fn count_primitive_tests(tests: u64) {
+    if ENABLED {
+        PRIMITIVE_TESTS.with(|count| count.set(count.get() + tests));
+    }
+}
+
+pub fn reset_counts() {
+    BOX_TESTS.with(|count| count.set(0));
+    PRIMITIVE_TESTS.with(|count| count.set(0));
+}
+
+/// Returns the counts accumulated since the last reset and clears them.
+pub fn take_counts() -> TraversalCounts {
+    let counts = TraversalCounts {
+        box_tests: BOX_TESTS.with(Cell::get),
+        primitive_tests: PRIMITIVE_TESTS.with(Cell::get),
+    };
+    reset_counts();
+    counts
+}
+
+/// Maps `value` in `[0, max]` onto a black-blue-green-yellow-red ramp.
+pub fn heatmap_color(value: f64, max: f64) -> Color {
+    const RAMP: [Color; 5] = [
+        Vec3(0.0, 0.0, 0.0),
+        Vec3(0.0, 0.0, 1.0),
+        Vec3(0.0, 1.0, 0.0),
+        Vec3(1.0, 1.0, 0.0),
+        Vec3(1.0, 0.0, 0.0),
+    ];
+
+    if max <= 0.0 {
+        return RAMP[0];
+    }
+
+    let t = crate::clamp(value / max, 0.0, 1.0) * (RAMP.len() - 1) as f64;
+    let i = usize::min(t as usize, RAMP.len() - 2);
+    let f = t - i as f64;
+
+    (1.0 - f) * RAMP[i] + f * RAMP[i + 1]
+}
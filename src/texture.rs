@@ -1,13 +1,34 @@
 use crate::{
     clamp,
+    hittable::{HitRecord, UvDerivatives},
     noise::Perlin,
     vec3::{Color, Vec3},
 };
-use image::{DynamicImage, GenericImageView};
-use std::{path::Path, rc::Rc};
+use image::{hdr::HdrDecoder, DynamicImage, GenericImageView, ImageError, ImageFormat, Rgb, Rgba};
+use std::{
+    cell::OnceCell,
+    env,
+    error::Error,
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 pub trait Texture {
     fn value(&self, u: f64, v: f64, p: Vec3) -> Color;
+
+    // The value at a hit, where textures that filter can use how much of the
+    // surface the pixel covers.
+    fn value_at(&self, record: &HitRecord) -> Color {
+        self.value(record.u, record.v, record.p)
+    }
+
+    // Coverage in [0, 1], for textures that carry an alpha channel.
+    fn alpha(&self, _u: f64, _v: f64, _p: Vec3) -> f64 {
+        1.0
+    }
 }
 
 pub struct SolidColor {
@@ -20,6 +41,12 @@ impl SolidColor {
     }
 }
 
+impl From<f64> for SolidColor {
+    fn from(value: f64) -> Self {
+        SolidColor::new(Vec3(value, value, value))
+    }
+}
+
 impl Texture for SolidColor {
     fn value(&self, _u: f64, _v: f64, _p: Vec3) -> Color {
         self.color
@@ -82,7 +109,7 @@ impl NoiseTexture {
 
 impl Texture for NoiseTexture {
     fn value(&self, _u: f64, _v: f64, p: Vec3) -> Color {
-        return self.color * (self.scale * p.z() + 10.0 * self.noise.turb(p)).sin().abs();
+        self.color * (self.scale * p.z() + 10.0 * self.noise.turb(p)).sin().abs()
     }
 }
 
@@ -96,29 +123,663 @@ impl Default for NoiseTexture {
     }
 }
 
+#[derive(Debug)]
+pub enum TextureError {
+    NotFound {
+        path: PathBuf,
+        searched: Vec<PathBuf>,
+    },
+    Image {
+        path: PathBuf,
+        error: ImageError,
+    },
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::NotFound { path, searched } => {
+                write!(f, "could not find texture {}", path.display())?;
+                if !searched.is_empty() {
+                    let searched: Vec<_> = searched
+                        .iter()
+                        .map(|dir| dir.display().to_string())
+                        .collect();
+                    write!(f, " (searched {})", searched.join(", "))?;
+                }
+                Ok(())
+            }
+            TextureError::Image { path, error } => {
+                write!(f, "could not load texture {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl Error for TextureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TextureError::NotFound { .. } => None,
+            TextureError::Image { error, .. } => Some(error),
+        }
+    }
+}
+
+// Directories to look in for relative asset paths that don't exist as given,
+// earliest first.
+#[derive(Debug, Clone)]
+pub struct SearchPath {
+    dirs: Vec<PathBuf>,
+}
+
+impl SearchPath {
+    pub fn new() -> Self {
+        SearchPath { dirs: Vec::new() }
+    }
+
+    // Adds `dir` after the directories already searched.
+    pub fn with_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    // Searches the directory holding `scene` before anything else, so scenes can
+    // name textures relative to themselves.
+    pub fn for_scene<P: AsRef<Path>>(mut self, scene: P) -> Self {
+        if let Some(dir) = scene.as_ref().parent() {
+            self.dirs.insert(0, dir.to_path_buf());
+        }
+        self
+    }
+
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, TextureError> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Ok(path.to_path_buf());
+        }
+
+        if path.is_relative() {
+            if let Some(found) = self
+                .dirs
+                .iter()
+                .map(|dir| dir.join(path))
+                .find(|candidate| candidate.exists())
+            {
+                return Ok(found);
+            }
+        }
+
+        Err(TextureError::NotFound {
+            path: path.to_path_buf(),
+            searched: self.dirs.clone(),
+        })
+    }
+}
+
+// `$RAYTRACER_RES` if it is set, then `res` in the working directory, then `res`
+// next to the executable, so an installed binary finds the assets shipped with it.
+impl Default for SearchPath {
+    fn default() -> Self {
+        let mut search_path = SearchPath::new();
+        if let Some(dir) = env::var_os("RAYTRACER_RES") {
+            search_path = search_path.with_dir(dir);
+        }
+
+        search_path = search_path.with_dir("res");
+        let exe_dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        match exe_dir {
+            Some(dir) => search_path.with_dir(dir.join("res")),
+            None => search_path,
+        }
+    }
+}
+
+// How texels are blended between texel centres.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    // Catmull-Rom over the nearest 4 x 4 texels: sharper than bilinear when
+    // magnified, at four times the lookups.
+    Bicubic,
+}
+
+// What lies outside [0, 1] in u or v.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    Mirror,
+    Clamp,
+}
+
+impl Wrap {
+    fn apply(self, i: i64, size: u32) -> u32 {
+        let size = size as i64;
+        let i = match self {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            Wrap::Clamp => i.max(0).min(size - 1),
+        };
+        i as u32
+    }
+}
+
+// How the colour channels of an image file are encoded. Alpha is always linear.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorSpace {
+    // Gamma encoded, as nearly all 8-bit photos and painted colour maps are.
+    Srgb,
+    // Stored as is, for float images and for data such as roughness, heights or
+    // normals.
+    Linear,
+}
+
+impl ColorSpace {
+    pub(crate) fn decode(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb if value <= 0.04045 => value / 12.92,
+            ColorSpace::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            ColorSpace::Linear => value,
+        }
+    }
+
+    pub(crate) fn encode(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb if value <= 0.003_130_8 => value * 12.92,
+            ColorSpace::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            ColorSpace::Linear => value,
+        }
+    }
+}
+
+// RGBA texels row by row from the top. 8-bit images keep the bytes they were
+// stored with, a quarter of the memory of floats, and are decoded as they are
+// read; float images are linear and can go above 1.
+struct Texels {
+    width: u32,
+    height: u32,
+    data: TexelData,
+}
+
+enum TexelData {
+    Bytes(Vec<[u8; 4]>),
+    Floats(Vec<[f32; 4]>),
+}
+
+impl From<&DynamicImage> for Texels {
+    fn from(image: &DynamicImage) -> Self {
+        let (width, height) = image.dimensions();
+        let data = image
+            .to_rgba()
+            .pixels()
+            .map(|&Rgba(channels)| channels)
+            .collect();
+
+        Texels {
+            width,
+            height,
+            data: TexelData::Bytes(data),
+        }
+    }
+}
+
+impl Texels {
+    fn from_hdr(width: u32, height: u32, pixels: Vec<Rgb<f32>>) -> Self {
+        let data = pixels
+            .into_iter()
+            .map(|Rgb([r, g, b])| [r, g, b, 1.0])
+            .collect();
+
+        Texels {
+            width,
+            height,
+            data: TexelData::Floats(data),
+        }
+    }
+
+    // The linear value of the texel at `index`, with `byte_values` giving the
+    // linear value of each byte of an 8-bit image's colour channels.
+    fn get(&self, index: usize, byte_values: &[f32; 256]) -> [f32; 4] {
+        match &self.data {
+            TexelData::Bytes(data) => {
+                let [r, g, b, a] = data[index];
+                [
+                    byte_values[r as usize],
+                    byte_values[g as usize],
+                    byte_values[b as usize],
+                    a as f32 / 255.0,
+                ]
+            }
+            TexelData::Floats(data) => data[index],
+        }
+    }
+
+    // The same texels with their colour encoded in `to` rather than `from`. Bytes
+    // are kept as they are, since they are only decoded when read.
+    fn reinterpret(self, from: ColorSpace, to: ColorSpace) -> Texels {
+        let data = match self.data {
+            TexelData::Bytes(data) => TexelData::Bytes(data),
+            TexelData::Floats(data) => {
+                let f = |value: f32| to.decode(from.encode(value));
+                TexelData::Floats(
+                    data.into_iter()
+                        .map(|[r, g, b, a]| [f(r), f(g), f(b), a])
+                        .collect(),
+                )
+            }
+        };
+
+        Texels { data, ..self }
+    }
+
+    // Half the size on each axis, each texel the mean of the linear values of
+    // the up to four it covers, stored the same way as this level.
+    fn downsample(&self, color_space: ColorSpace, byte_values: &[f32; 256]) -> Texels {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let at = |x: u32, y: u32| {
+            let index = y.min(self.height - 1) * self.width + x.min(self.width - 1);
+            self.get(index as usize, byte_values)
+        };
+
+        let mut means = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let corners = [
+                    at(2 * x, 2 * y),
+                    at(2 * x + 1, 2 * y),
+                    at(2 * x, 2 * y + 1),
+                    at(2 * x + 1, 2 * y + 1),
+                ];
+                let mut texel = [0.0; 4];
+                for corner in &corners {
+                    for (total, value) in texel.iter_mut().zip(corner) {
+                        *total += 0.25 * value;
+                    }
+                }
+                means.push(texel);
+            }
+        }
+
+        let data = match self.data {
+            TexelData::Bytes(_) => {
+                let byte = |value: f32| (clamp(value as f64, 0.0, 1.0) * 255.0).round() as u8;
+                TexelData::Bytes(
+                    means
+                        .into_iter()
+                        .map(|[r, g, b, a]| {
+                            let encode = |value| byte(color_space.encode(value));
+                            [encode(r), encode(g), encode(b), byte(a)]
+                        })
+                        .collect(),
+                )
+            }
+            TexelData::Floats(_) => TexelData::Floats(means),
+        };
+
+        Texels {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+// The linear value of each byte of an 8-bit colour channel in `color_space`.
+fn byte_values(color_space: ColorSpace) -> [f32; 256] {
+    let mut values = [0.0; 256];
+    for (byte, value) in values.iter_mut().enumerate() {
+        *value = color_space.decode(byte as f32 / 255.0);
+    }
+    values
+}
+
+// An image with a mip pyramid. Hits that carry `uv_derivatives` read from the
+// levels whose texels are about the size of the pixel, blending between the two
+// nearest; other hits read the full-size image. The smaller levels are only built
+// the first time they are needed.
+//
+// 8-bit images are taken to be sRGB unless set otherwise with `with_color_space`,
+// and Radiance `.hdr` images are read as linear floats. OpenEXR isn't supported by
+// the version of `image` this crate uses.
 pub struct ImageTexture {
-    image: DynamicImage,
+    base: Texels,
+    // Half size and smaller, down to 1 x 1.
+    mips: OnceCell<Vec<Texels>>,
+    color_space: ColorSpace,
+    byte_values: [f32; 256],
+    pub filter: Filter,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
 }
 
 impl ImageTexture {
-    pub fn new<P: AsRef<Path>>(path: P) -> Self {
-        let image = image::open(path).unwrap();
-        Self { image }
+    // Loads `path`, looking in the default `SearchPath` if it is relative and
+    // not found as given.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, TextureError> {
+        Self::find(path, &SearchPath::default())
+    }
+
+    pub fn find<P: AsRef<Path>>(path: P, search_path: &SearchPath) -> Result<Self, TextureError> {
+        let path = search_path.resolve(path)?;
+        let texture = match ImageFormat::from_path(&path) {
+            Ok(ImageFormat::Hdr) => Self::open_hdr(&path),
+            _ => image::open(&path).map(|image| Self::from(&image)),
+        };
+
+        texture.map_err(|error| TextureError::Image { path, error })
+    }
+
+    // `image::open` would clamp a Radiance file to 8 bits, so it is decoded here.
+    fn open_hdr(path: &Path) -> Result<Self, ImageError> {
+        let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+
+        let texels = Texels::from_hdr(metadata.width, metadata.height, pixels);
+        Ok(Self::from_texels(texels, ColorSpace::Linear))
+    }
+
+    // Reinterprets the image's colour as encoded in `color_space`, such as
+    // `Linear` for a roughness map saved as an 8-bit PNG.
+    pub fn with_color_space(self, color_space: ColorSpace) -> Self {
+        if color_space == self.color_space {
+            return self;
+        }
+
+        let texels = self.base.reinterpret(self.color_space, color_space);
+        ImageTexture {
+            filter: self.filter,
+            wrap_u: self.wrap_u,
+            wrap_v: self.wrap_v,
+            ..Self::from_texels(texels, color_space)
+        }
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    // `base` holds floats already decoded to linear, or bytes in `color_space`.
+    fn from_texels(base: Texels, color_space: ColorSpace) -> Self {
+        ImageTexture {
+            base,
+            mips: OnceCell::new(),
+            color_space,
+            byte_values: byte_values(color_space),
+            filter: Filter::Bilinear,
+            wrap_u: Wrap::Clamp,
+            wrap_v: Wrap::Clamp,
+        }
+    }
+
+    fn mips(&self) -> &[Texels] {
+        self.mips.get_or_init(|| {
+            let mut mips: Vec<Texels> = Vec::new();
+            loop {
+                let last = mips.last().unwrap_or(&self.base);
+                if last.width == 1 && last.height == 1 {
+                    break mips;
+                }
+                let next = last.downsample(self.color_space, &self.byte_values);
+                mips.push(next);
+            }
+        })
+    }
+
+    // Level 0 is the full-size image.
+    fn level(&self, index: usize) -> &Texels {
+        match index {
+            0 => &self.base,
+            _ => &self.mips()[index - 1],
+        }
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Self {
+        ImageTexture { filter, ..self }
+    }
+
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        ImageTexture {
+            wrap_u: wrap,
+            wrap_v: wrap,
+            ..self
+        }
+    }
+
+    // Sets how u alone wraps, such as `Repeat` for a map wrapped around a sphere,
+    // whose u runs round the equator and whose v stops at the poles.
+    pub fn with_wrap_u(self, wrap_u: Wrap) -> Self {
+        ImageTexture { wrap_u, ..self }
+    }
+
+    pub fn with_wrap_v(self, wrap_v: Wrap) -> Self {
+        ImageTexture { wrap_v, ..self }
     }
 }
 
+// Decodes the image as sRGB.
+impl From<&DynamicImage> for ImageTexture {
+    fn from(image: &DynamicImage) -> Self {
+        Self::from_texels(Texels::from(image), ColorSpace::Srgb)
+    }
+}
+
+impl ImageTexture {
+    fn texel(&self, level: &Texels, x: i64, y: i64) -> [f64; 4] {
+        let x = self.wrap_u.apply(x, level.width);
+        let y = self.wrap_v.apply(y, level.height);
+
+        let texel = level.get((y * level.width + x) as usize, &self.byte_values);
+        [
+            texel[0] as f64,
+            texel[1] as f64,
+            texel[2] as f64,
+            texel[3] as f64,
+        ]
+    }
+
+    fn sample(&self, level: &Texels, u: f64, v: f64) -> [f64; 4] {
+        // Texel centres sit at half-integer coordinates.
+        let x = u * level.width as f64;
+        let y = (1.0 - v) * level.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(level, x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => self.weighted(level, x, y, 1, |t| [1.0 - t, t]),
+            Filter::Bicubic => {
+                let [r, g, b, a] = self.weighted(level, x, y, 2, catmull_rom);
+                // The negative lobes can overshoot past the texels' range.
+                [r.max(0.0), g.max(0.0), b.max(0.0), clamp(a, 0.0, 1.0)]
+            }
+        }
+    }
+
+    // Sums the `2 * radius` texels either side of (x, y) on each axis, weighted
+    // by `weights` of the offset past the texel centre just below.
+    fn weighted<W, F>(&self, level: &Texels, x: f64, y: f64, radius: i64, weights: F) -> [f64; 4]
+    where
+        W: AsRef<[f64]>,
+        F: Fn(f64) -> W,
+    {
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (wx, wy) = (weights(x - x0), weights(y - y0));
+        let (x0, y0) = (x0 as i64 - radius + 1, y0 as i64 - radius + 1);
+
+        let mut sum = [0.0; 4];
+        for (j, &wy) in wy.as_ref().iter().enumerate() {
+            for (i, &wx) in wx.as_ref().iter().enumerate() {
+                let texel = self.texel(level, x0 + i as i64, y0 + j as i64);
+                for (total, value) in sum.iter_mut().zip(&texel) {
+                    *total += wx * wy * value;
+                }
+            }
+        }
+        sum
+    }
+
+    // Samples the pyramid at the level where a texel spans the longer side of the
+    // pixel's footprint.
+    fn sample_footprint(&self, u: f64, v: f64, derivatives: &UvDerivatives) -> [f64; 4] {
+        let base = &self.base;
+        let (width, height) = (base.width as f64, base.height as f64);
+        let x_extent = (derivatives.dudx * width).hypot(derivatives.dvdx * height);
+        let y_extent = (derivatives.dudy * width).hypot(derivatives.dvdy * height);
+
+        let last = self.mips().len() as f64;
+        let level = clamp(x_extent.max(y_extent).max(1.0).log2(), 0.0, last);
+        if self.filter == Filter::Nearest {
+            return self.sample(self.level(level.round() as usize), u, v);
+        }
+
+        let (fine, t) = (level.floor(), level - level.floor());
+        let lower = self.sample(self.level(fine as usize), u, v);
+        if t == 0.0 {
+            return lower;
+        }
+
+        let upper = self.sample(self.level(fine as usize + 1), u, v);
+        let mut blended = [0.0; 4];
+        for ((value, a), b) in blended.iter_mut().zip(&lower).zip(&upper) {
+            *value = a + t * (b - a);
+        }
+        blended
+    }
+}
+
+// Catmull-Rom weights for the four texels around an offset `t` in [0, 1) past
+// the second one.
+fn catmull_rom(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
 impl Texture for ImageTexture {
     fn value(&self, u: f64, v: f64, _p: Vec3) -> Color {
-        let u = clamp(u, 0.0, 1.0);
-        let v = 1.0 - clamp(v, 0.0, 1.0);
+        let [r, g, b, _] = self.sample(&self.base, u, v);
+        Vec3(r, g, b)
+    }
+
+    fn value_at(&self, record: &HitRecord) -> Color {
+        let [r, g, b, _] = match &record.uv_derivatives {
+            Some(derivatives) => self.sample_footprint(record.u, record.v, derivatives),
+            None => self.sample(&self.base, record.u, record.v),
+        };
+        Vec3(r, g, b)
+    }
+
+    fn alpha(&self, u: f64, v: f64, _p: Vec3) -> f64 {
+        self.sample(&self.base, u, v)[3]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    // A black texel on the left and a white one on the right, read as linear.
+    fn black_and_white() -> ImageTexture {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        ImageTexture::from(&DynamicImage::ImageRgb8(image)).with_color_space(ColorSpace::Linear)
+    }
 
-        let (width, height) = self.image.dimensions();
+    #[test]
+    fn wrap_modes_map_texel_indices_into_the_image() {
+        let indices = |wrap: Wrap| (-5..9).map(|i| wrap.apply(i, 4)).collect::<Vec<_>>();
 
-        let x = (u * width as f64) as u32;
-        let y = (v * height as f64) as u32;
+        assert_eq!(
+            indices(Wrap::Repeat),
+            [3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0]
+        );
+        assert_eq!(
+            indices(Wrap::Mirror),
+            [3, 3, 2, 1, 0, 0, 1, 2, 3, 3, 2, 1, 0, 0]
+        );
+        assert_eq!(
+            indices(Wrap::Clamp),
+            [0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn eight_bit_images_keep_their_bytes_and_build_mips_when_needed() {
+        let mut image = RgbImage::new(2, 1);
+        image.put_pixel(1, 0, Rgb([255, 255, 255]));
+        let texture = ImageTexture::from(&DynamicImage::ImageRgb8(image));
+
+        assert!(matches!(texture.base.data, TexelData::Bytes(_)));
+        assert!(texture.mips.get().is_none());
+
+        // A pixel footprint as wide as the image reads the 1 x 1 level, which
+        // averages black and white in linear light rather than in sRGB bytes.
+        let derivatives = UvDerivatives {
+            dudx: 1.0,
+            dvdx: 0.0,
+            dudy: 0.0,
+            dvdy: 1.0,
+        };
+        let [r, _, _, a] = texture.sample_footprint(0.5, 0.5, &derivatives);
+        assert_eq!(texture.mips().len(), 1);
+        assert!(matches!(texture.mips()[0].data, TexelData::Bytes(_)));
+        assert!((r - 0.5).abs() < 0.01, "{}", r);
+        assert_eq!(a, 1.0);
+    }
+
+    #[test]
+    fn repeating_u_blends_across_the_seam() {
+        let at_seam = |texture: &ImageTexture| texture.value(0.0, 0.5, Vec3::default()).x();
+
+        let assert_close = |actual: f64, expected: f64| {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "{} != {}",
+                actual,
+                expected
+            );
+        };
+
+        // Clamped, the left edge is the black texel alone; repeated, it is half
+        // way to the white texel on the far side.
+        assert_close(at_seam(&black_and_white()), 0.0);
+        assert_close(at_seam(&black_and_white().with_wrap_u(Wrap::Repeat)), 0.5);
+        assert_close(at_seam(&black_and_white().with_wrap(Wrap::Repeat)), 0.5);
+        assert_close(at_seam(&black_and_white().with_wrap_v(Wrap::Repeat)), 0.0);
+
+        // The wrap survives reinterpreting the colour space.
+        let texture = black_and_white()
+            .with_wrap_u(Wrap::Repeat)
+            .with_color_space(ColorSpace::Srgb);
+        assert_eq!(texture.wrap_u, Wrap::Repeat);
+        assert_eq!(texture.wrap_v, Wrap::Clamp);
+    }
 
-        let pixel = self.image.get_pixel(x, y);
+    #[test]
+    fn the_default_search_path_is_relative_to_where_it_runs() {
+        let search_path = SearchPath::default();
+        let exe_dir = env::current_exe().unwrap().parent().unwrap().join("res");
 
-        Vec3::from(pixel)
+        assert!(search_path.dirs.contains(&PathBuf::from("res")));
+        assert!(search_path.dirs.contains(&exe_dir));
+        assert!(!search_path
+            .dirs
+            .contains(&Path::new(env!("CARGO_MANIFEST_DIR")).join("res")));
     }
 }
@@ -0,0 +1,158 @@
+use crate::degrees_to_radians;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::vec3::{Color, Vec3};
+
+// Snaps `x` in [0, 1] to one of `bands` evenly spaced levels, the darkest 0 and the
+// brightest 1.
+pub fn quantise(x: f64, bands: u32) -> f64 {
+    if bands < 2 {
+        return 1.0;
+    }
+
+    let band = (x.max(0.0) * bands as f64).floor().min(bands as f64 - 1.0);
+    band / (bands as f64 - 1.0)
+}
+
+// The colour of a hit for the toon integrator: what the material emits, which is
+// how `Toon` materials draw themselves, plus for other materials their albedo
+// under a banded headlight. Nothing is sampled, so flat areas stay flat.
+pub fn shade(ray: &Ray, record: &HitRecord) -> Color {
+    let emitted = record.mat_ptr.emitted(ray, record);
+    let albedo = match record.mat_ptr.albedo(record) {
+        Some(albedo) => albedo,
+        None => return emitted,
+    };
+
+    let facing = Vec3::dot(record.normal, -Vec3::unit_vector(ray.direction()));
+    emitted + (0.3 + 0.7 * quantise(facing, 3)) * albedo
+}
+
+// What the first hit of a pixel's centre ray looked like, for finding edges.
+#[derive(Debug, Copy, Clone)]
+pub struct Surface {
+    pub distance: f64,
+    pub normal: Vec3,
+}
+
+impl Surface {
+    pub fn new(ray: &Ray, record: &HitRecord) -> Self {
+        Surface {
+            distance: record.t * ray.direction().length(),
+            normal: record.normal,
+        }
+    }
+}
+
+// Finds the pixels to ink: silhouettes, where a surface meets the background or
+// one much further away, and creases, where the normal turns sharply.
+#[derive(Debug, Copy, Clone)]
+pub struct OutlinePass {
+    // Relative jump in distance between neighbouring pixels that counts as a
+    // silhouette.
+    pub depth_threshold: f64,
+    // Angle in degrees between neighbouring normals that counts as a crease.
+    pub crease_angle: f64,
+    pub color: Color,
+}
+
+impl Default for OutlinePass {
+    fn default() -> Self {
+        OutlinePass {
+            depth_threshold: 0.1,
+            crease_angle: 40.0,
+            color: Vec3(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl OutlinePass {
+    // One flag per pixel, row by row, for a `width` by `height` buffer of
+    // surfaces where `None` means the centre ray missed. Only the nearer side of
+    // a silhouette is inked, so lines hug the front object.
+    pub fn edges(&self, surfaces: &[Option<Surface>], width: u32, height: u32) -> Vec<bool> {
+        let (width, height) = (width as usize, height as usize);
+        let cos_crease = degrees_to_radians(self.crease_angle).cos();
+
+        (0..surfaces.len())
+            .map(|i| {
+                let surface = match surfaces[i] {
+                    Some(surface) => surface,
+                    None => return false,
+                };
+                let (x, y) = (i % width, i / width);
+
+                // Neighbours either side along each axis, clamped to the image.
+                let axes = [
+                    (
+                        y * width + x.saturating_sub(1),
+                        y * width + (x + 1).min(width - 1),
+                    ),
+                    (
+                        y.saturating_sub(1) * width + x,
+                        (y + 1).min(height - 1) * width + x,
+                    ),
+                ];
+
+                axes.iter().any(
+                    |&(before, after)| match (surfaces[before], surfaces[after]) {
+                        (Some(before), Some(after)) => {
+                            self.is_edge(surface, before, after, cos_crease)
+                        }
+                        _ => true,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn is_edge(&self, surface: Surface, before: Surface, after: Surface, cos_crease: f64) -> bool {
+        if Vec3::dot(surface.normal, before.normal) < cos_crease
+            || Vec3::dot(surface.normal, after.normal) < cos_crease
+        {
+            return true;
+        }
+
+        // Inverse distance is close to linear across the screen on any flat
+        // surface, however steeply it is viewed, so a large second difference
+        // means a jump rather than a slope.
+        let inverse = 1.0 / surface.distance;
+        let bulge = 2.0 * inverse - 1.0 / before.distance - 1.0 / after.distance;
+        bulge > self.depth_threshold * inverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Dielectric, Lambertian, Material};
+    use std::rc::Rc;
+
+    fn shade_head_on(material: Rc<dyn Material>) -> Color {
+        let ray = Ray::new(Vec3(0.0, 0.0, 1.0), Vec3(0.0, 0.0, -1.0), 0.0);
+        let record = HitRecord::new(
+            Vec3(0.0, 0.0, 0.0),
+            1.0,
+            0.5,
+            0.5,
+            Vec3(0.0, 0.0, 1.0),
+            &material,
+            &ray,
+        );
+        shade(&ray, &record)
+    }
+
+    #[test]
+    fn shading_uses_the_albedo_and_does_not_flicker() {
+        let color = shade_head_on(Rc::new(Lambertian::from((0.2, 0.4, 0.6))));
+        assert_eq!((color.x(), color.y(), color.z()), (0.2, 0.4, 0.6));
+
+        // Glass picks reflection or refraction at random when scattered, but its
+        // toon colour is the same every time.
+        let glass: Rc<dyn Material> = Rc::new(Dielectric::new(1.5));
+        for _ in 0..100 {
+            let color = shade_head_on(glass.clone());
+            assert_eq!((color.x(), color.y(), color.z()), (1.0, 1.0, 1.0));
+        }
+    }
+}
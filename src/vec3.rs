@@ -1,6 +1,7 @@
 use std::fmt;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+use crate::texture::ColorSpace;
 use crate::*;
 use image::{Rgb, Rgba};
 
@@ -33,6 +34,14 @@ impl Vec3 {
         Vec3(r * a.cos(), r * a.sin(), z)
     }
 
+    /// A random direction about +z with density proportional to its z component.
+    pub fn rand_cosine_direction() -> Self {
+        let a = rand_with_range(0.0, 2.0 * PI);
+        let r2 = random_double();
+        let r = r2.sqrt();
+        Vec3(r * a.cos(), r * a.sin(), (1.0 - r2).sqrt())
+    }
+
     pub fn rand_in_unit_sphere() -> Self {
         loop {
             let p = Vec3::rand_with_range(-1.0, 1.0);
@@ -82,6 +91,11 @@ impl Vec3 {
         self.0 * self.0 + self.1 * self.1 + self.2 * self.2
     }
 
+    /// Rec. 709 luminance, for reading a colour as a single intensity.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.0 + 0.7152 * self.1 + 0.0722 * self.2
+    }
+
     pub fn dot(lhs: Self, rhs: Self) -> f64 {
         lhs.0 * rhs.0 + lhs.1 * rhs.1 + lhs.2 * rhs.2
     }
@@ -103,6 +117,7 @@ impl Vec3 {
     }
 }
 
+// Takes the bytes as linear values, unlike pixels from images below.
 impl From<(u8, u8, u8)> for Vec3 {
     fn from((r, g, b): (u8, u8, u8)) -> Self {
         let color_scale = 1.0 / 255.0;
@@ -115,15 +130,18 @@ impl From<(u8, u8, u8)> for Vec3 {
     }
 }
 
+// Image pixels are sRGB encoded, so they are decoded to linear colour. Alpha is
+// dropped.
 impl From<Rgba<u8>> for Vec3 {
     fn from(pixel: Rgba<u8>) -> Self {
-        Vec3::from((pixel[0], pixel[1], pixel[2]))
+        Vec3::from(Rgb([pixel[0], pixel[1], pixel[2]]))
     }
 }
 
 impl From<Rgb<u8>> for Vec3 {
-    fn from(pixel: Rgb<u8>) -> Self {
-        Vec3::from((pixel[0], pixel[1], pixel[2]))
+    fn from(Rgb([r, g, b]): Rgb<u8>) -> Self {
+        let decode = |byte: u8| ColorSpace::Srgb.decode(byte as f32 / 255.0) as f64;
+        Vec3(decode(r), decode(g), decode(b))
     }
 }
 
@@ -251,3 +269,27 @@ pub fn refract(uv: Vec3, n: Vec3, etai_over_etat: f64) -> Vec3 {
     let r_out_perp = -f64::sqrt(1.0 - r_out_parellel.length_squared()) * n;
     r_out_parellel + r_out_perp
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_pixels_are_decoded_from_srgb() {
+        let color = Vec3::from(Rgb([0, 128, 255]));
+        assert_eq!(color.x(), 0.0);
+        assert!((color.y() - 0.2158605).abs() < 1e-6, "{}", color.y());
+        assert!((color.z() - 1.0).abs() < 1e-6, "{}", color.z());
+
+        // Low values are on the linear segment of the curve.
+        let color = Vec3::from(Rgba([10, 10, 10, 0]));
+        assert!(
+            (color.x() - 10.0 / 255.0 / 12.92).abs() < 1e-6,
+            "{}",
+            color.x()
+        );
+
+        let color = Vec3::from((128, 128, 128));
+        assert_eq!(color.x(), 128.0 / 255.0);
+    }
+}